@@ -0,0 +1,306 @@
+use nom::{
+    branch::alt,
+    character::complete::char,
+    combinator::{cut, map, opt},
+    error::context,
+    multi::many0,
+    sequence::{delimited, pair, preceded, terminated, tuple},
+};
+
+use crate::{
+    expression_parsers::logical_parser,
+    factors_parsers::keyword,
+    operator_parsers::unparenthesized,
+    span::{spanned, Input, ParseResult},
+    statement_parsers::{empty_statements, terminated_statement},
+    token::Token,
+    trivia_parsers::{trailing_trivia, trivia},
+};
+
+/// Parser for a `Block`: any number of statements between `{` and `}`, each
+/// ended by a `;` or a newline, or by the `}` for the last one. The value of
+/// a block is the value of its last statement.
+///
+/// # Example
+///
+/// ```
+/// use lrvmism::block_parsers::block_parser;
+/// use lrvmism::token::Token;
+/// let (_reminder, block) = block_parser("{ let x = 1\n x + 1 }".into()).unwrap();
+/// match block {
+///     Token::Block { statements, .. } => assert_eq!(2, statements.len()),
+///     other => panic!("expected a block, got {:?}", other),
+/// }
+/// ```
+pub fn block_parser(input: Input) -> ParseResult<Token> {
+    context(
+        "block_parser",
+        preceded(
+            trivia,
+            map(
+                spanned(delimited(
+                    char('{'),
+                    unparenthesized(terminated(many0(terminated_statement), empty_statements)),
+                    char('}'),
+                )),
+                |(statements, span)| Token::Block { statements, span },
+            ),
+        ),
+    )(input)
+}
+
+/// Parser for `if <condition> { ... }`, optionally followed by `else { ... }`
+/// or `else if ...`. An `If` is an expression, so it can be used wherever a
+/// value is expected.
+///
+/// # Example
+///
+/// ```
+/// use lrvmism::block_parsers::if_parser;
+/// let result = if_parser("if a > b { a } else if a < b { b } else { 0 }".into());
+/// assert!(result.is_ok());
+/// assert!(result.unwrap().0.is_empty());
+/// ```
+pub fn if_parser(input: Input) -> ParseResult<Token> {
+    context(
+        "if_parser",
+        preceded(
+            trivia,
+            map(
+                pair(
+                    spanned(keyword("if")),
+                    cut(tuple((
+                        logical_parser,
+                        block_parser,
+                        opt(preceded(
+                            preceded(trivia, keyword("else")),
+                            alt((if_parser, block_parser)),
+                        )),
+                        trailing_trivia,
+                    ))),
+                ),
+                |((_, start), (condition, body, alternative, _))| Token::If {
+                    span: start.to(alternative
+                        .as_ref()
+                        .unwrap_or(&body)
+                        .span()
+                        .expect("blocks are nodes")),
+                    condition: Box::new(condition),
+                    body: Box::new(body),
+                    alternative: alternative.map(Box::new),
+                },
+            ),
+        ),
+    )(input)
+}
+
+/// Parser for `while <condition> { ... }`, which runs its block for as long
+/// as the condition is not 0.
+///
+/// # Example
+///
+/// ```
+/// use lrvmism::block_parsers::while_parser;
+/// let result = while_parser("while i < 10 { i += 1 }".into());
+/// assert!(result.is_ok());
+/// assert!(result.unwrap().0.is_empty());
+/// ```
+pub fn while_parser(input: Input) -> ParseResult<Token> {
+    context(
+        "while_parser",
+        preceded(
+            trivia,
+            map(
+                pair(
+                    spanned(keyword("while")),
+                    cut(tuple((logical_parser, block_parser, trailing_trivia))),
+                ),
+                |((_, start), (condition, body, _))| Token::While {
+                    span: start.to(body.span().expect("blocks are nodes")),
+                    condition: Box::new(condition),
+                    body: Box::new(body),
+                },
+            ),
+        ),
+    )(input)
+}
+
+/// Parser for `break`, which leaves the innermost loop.
+pub fn break_parser(input: Input) -> ParseResult<Token> {
+    context(
+        "break_parser",
+        preceded(
+            trivia,
+            terminated(
+                map(spanned(keyword("break")), |(_, span)| Token::Break { span }),
+                trailing_trivia,
+            ),
+        ),
+    )(input)
+}
+
+/// Parser for `continue`, which jumps to the next iteration of the innermost
+/// loop.
+pub fn continue_parser(input: Input) -> ParseResult<Token> {
+    context(
+        "continue_parser",
+        preceded(
+            trivia,
+            terminated(
+                map(spanned(keyword("continue")), |(_, span)| Token::Continue {
+                    span,
+                }),
+                trailing_trivia,
+            ),
+        ),
+    )(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{span::Span, token::Token};
+
+    use super::{block_parser, break_parser, continue_parser, if_parser, while_parser};
+
+    #[test]
+    fn test_block_parser() {
+        for (input, count) in [
+            ("{}", 0),
+            ("{ 1 }", 1),
+            ("{\n  let x = 1\n  x = 2\n  x\n}", 3),
+        ] {
+            let result = block_parser(input.into());
+            assert!(result.is_ok(), "{}", input);
+            let (r, block) = result.unwrap();
+            assert!(r.is_empty());
+            match block {
+                Token::Block { statements, .. } => assert_eq!(count, statements.len()),
+                other => panic!("expected a block, got {:?}", other),
+            }
+        }
+        assert!(block_parser("{ 1".into()).is_err());
+    }
+
+    #[test]
+    fn test_if_parser() {
+        let (r, token) = if_parser("if x > 1 { 1 }".into()).unwrap();
+        assert!(r.is_empty());
+        match token {
+            Token::If {
+                condition,
+                alternative,
+                ..
+            } => {
+                assert_eq!("x > 1", condition.to_string());
+                assert!(alternative.is_none());
+            }
+            other => panic!("expected an if, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_else_if_chain() {
+        let source = "if x == 1 {\n  10\n} else if x == 2 {\n  20\n}\nelse {\n  30\n}";
+        let (r, token) = if_parser(source.into()).unwrap();
+        assert!(r.is_empty());
+        match token {
+            Token::If {
+                alternative: Some(alternative),
+                ..
+            } => match *alternative {
+                Token::If {
+                    alternative: Some(last),
+                    ..
+                } => assert!(matches!(*last, Token::Block { .. })),
+                other => panic!("expected an else if, got {:?}", other),
+            },
+            other => panic!("expected an if, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_if_needs_a_keyword() {
+        assert!(if_parser("iffy { 1 }".into()).is_err());
+        assert!(if_parser("if x > 1".into()).is_err());
+    }
+
+    #[test]
+    fn test_while_parser() {
+        let (r, token) =
+            while_parser("while i < 10 {\n  i += 1\n  if i == 5 { break }\n}".into()).unwrap();
+        assert!(r.is_empty());
+        match token {
+            Token::While {
+                condition, body, ..
+            } => {
+                assert_eq!("i < 10", condition.to_string());
+                assert!(matches!(*body, Token::Block { .. }));
+            }
+            other => panic!("expected a while, got {:?}", other),
+        }
+        assert!(while_parser("whilex { 1 }".into()).is_err());
+    }
+
+    #[test]
+    fn test_break_and_continue_remember_where_they_are() {
+        let source = "  break\n";
+        let (r, token) = break_parser(source.into()).unwrap();
+        // The newline is left for what ends the statement
+        assert_eq!("\n", *r.fragment());
+        assert_eq!(
+            Token::Break {
+                span: Span {
+                    offset: 2,
+                    length: 5,
+                    line: 1,
+                    column: 3,
+                },
+            },
+            token
+        );
+
+        let (_, token) = continue_parser("continue".into()).unwrap();
+        assert_eq!(
+            Token::Continue {
+                span: Span {
+                    offset: 0,
+                    length: 8,
+                    line: 1,
+                    column: 1,
+                },
+            },
+            token
+        );
+        assert!(break_parser("breakfast".into()).is_err());
+    }
+
+    #[test]
+    fn test_spans_cover_the_whole_statement() {
+        let source = "if x { 1 }\nelse {\n  2 // two\n} // done";
+        let (_, token) = if_parser(source.into()).unwrap();
+        assert_eq!(
+            Some(Span {
+                offset: 0,
+                length: 30,
+                line: 1,
+                column: 1,
+            }),
+            token.span()
+        );
+        match token {
+            Token::If {
+                alternative: Some(alternative),
+                ..
+            } => assert_eq!(
+                Some(Span {
+                    offset: 16,
+                    length: 14,
+                    line: 2,
+                    column: 6,
+                }),
+                alternative.span()
+            ),
+            other => panic!("expected an if with an else, got {:?}", other),
+        }
+    }
+}
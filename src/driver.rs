@@ -0,0 +1,707 @@
+use std::fmt;
+
+use lrvm::vm::{VMEventType, VM};
+
+use crate::{
+    error::{CompileError, Diagnostics},
+    program_parsers::parse_program,
+    token::Token,
+    type_checker::{TypeChecker, Types},
+    vistor::{Compiler, Register, Visitor},
+};
+
+/// Parses `source` into a `Token::Program`. Every error that keeps it from
+/// parsing is reported at once, as `CompileError::Diagnostics`, including
+/// every `break`, `continue` or `return` with nothing to leave.
+pub fn parse(source: &str) -> Result<Token, CompileError> {
+    parse_program(source).map_err(CompileError::Diagnostics)
+}
+
+/// Type checks `program` with `checker`. Every type error is reported at
+/// once, as `CompileError::Diagnostics`, each with the node it is about.
+pub fn check(checker: &mut TypeChecker, program: &Token) -> Result<Types, CompileError> {
+    checker.check(program).map_err(|errors| {
+        CompileError::Diagnostics(Diagnostics {
+            errors: errors
+                .into_iter()
+                .map(|(span, error)| CompileError::Type { span, error })
+                .collect(),
+            program: program.clone(),
+        })
+    })
+}
+
+/// Parses and type checks `source`, then walks the tree with a fresh
+/// `Compiler`, stopping before anything is assembled.
+pub fn codegen(source: &str) -> Result<Compiler, CompileError> {
+    let program = parse(source)?;
+    let types = check(&mut TypeChecker::new(), &program)?;
+    let mut compiler = Compiler::new();
+    compiler.set_types(types);
+    compiler.visit_token(&program);
+    Ok(compiler)
+}
+
+/// Runs the whole pipeline: parses and type checks `source`, walks the tree
+/// with a fresh `Compiler` and assembles the result into lrvm bytecode,
+/// header included.
+pub fn compile(source: &str) -> Result<Vec<u8>, CompileError> {
+    codegen(source)?.compile()
+}
+
+/// How the VM stopped running a program
+#[derive(Debug, PartialEq)]
+pub enum ExitState {
+    /// The program reached a `HLT` (or otherwise stopped cleanly)
+    Halted { code: u32 },
+    /// The VM gave up on the program, e.g. on an illegal instruction
+    Crashed { code: u32 },
+    /// The VM did not report how it stopped
+    Unknown,
+}
+
+/// A value read back from the VM's registers
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value {
+    Int(i64),
+    Float(f64),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Int(value) => write!(f, "{}", value),
+            Value::Float(value) => write!(f, "{:?}", value),
+        }
+    }
+}
+
+/// The outcome of running a compiled program on the lrvm VM
+#[derive(Debug, PartialEq)]
+pub struct RunResult {
+    /// The value of the final expression's result register
+    pub value: Option<Value>,
+    /// How the VM stopped
+    pub exit: ExitState,
+}
+
+/// Compiles `source` and runs it to completion on a fresh lrvm `VM`.
+pub fn run(source: &str) -> Result<RunResult, CompileError> {
+    let mut compiler = codegen(source)?;
+    let bytecode = compiler.compile()?;
+    let mut vm = VM::new();
+    Ok(execute(&mut vm, bytecode, compiler.result_register()))
+}
+
+/// Loads `bytecode` into `vm`, runs it and reads back `result_register`.
+/// Registers are left as the program left them, so a long-lived `vm` keeps
+/// its state from one program to the next.
+pub fn execute(vm: &mut VM, bytecode: Vec<u8>, result_register: Option<Register>) -> RunResult {
+    vm.program = bytecode;
+    let events = vm.run();
+    let exit = events
+        .iter()
+        .rev()
+        .find_map(|e| match e.event {
+            VMEventType::GracefulStop { code } => Some(ExitState::Halted { code }),
+            VMEventType::Crash { code } => Some(ExitState::Crashed { code }),
+            _ => None,
+        })
+        .unwrap_or(ExitState::Unknown);
+    let value = result_register.map(|register| match register {
+        Register::Int(r) => Value::Int(i64::from(vm.registers[r as usize])),
+        Register::Float(r) => Value::Float(vm.float_registers[r as usize]),
+    });
+    RunResult { value, exit }
+}
+
+/// Formats `bytes` as a hex dump, sixteen bytes per line, each line prefixed
+/// with its offset and followed by the printable ASCII characters.
+pub fn hex_dump(bytes: &[u8]) -> String {
+    let mut dump = String::new();
+    for (i, chunk) in bytes.chunks(16).enumerate() {
+        let hex: Vec<String> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+        let ascii: String = chunk
+            .iter()
+            .map(|&b| {
+                if b.is_ascii_graphic() || b == b' ' {
+                    b as char
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        dump.push_str(&format!(
+            "{:08x}: {:<47}  |{}|\n",
+            i * 16,
+            hex.join(" "),
+            ascii
+        ));
+    }
+    dump
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{codegen, compile, hex_dump, parse, parse_program, run, ExitState, Value};
+    use crate::error::CompileError;
+    use crate::span::Span;
+    use crate::type_checker::Type;
+
+    #[test]
+    fn test_compile() {
+        let bytecode = compile("1+2");
+        assert!(bytecode.is_ok());
+        assert!(!bytecode.unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_run() {
+        let cases = [
+            ("1+2", 3),
+            ("(4*3)-1", 11),
+            ("8/2", 4),
+            ("10-2-3", 5),
+            ("2*3+4*5", 26),
+            ("-4+1", -3),
+            ("70000", 70000),
+            ("-70000*2", -140000),
+            ("1 -2", -1),
+            ("0xFF + 0b1010 - 0o17", 250),
+            ("1_000 * 2", 2000),
+            ("1 - -2", 3),
+            ("-(1+2)", -3),
+            ("2 * -3", -6),
+            ("- -4", 4),
+            ("+7 - +2", 5),
+            ("-2 ** 2", -4),
+            ("(-2) ** 2", 4),
+            ("let x = 5\n(-x) + +x * 2", 5),
+            ("1+2\n3*4", 12),
+            ("let x = 4\nlet y = x * 2\ny + x", 12),
+            ("let big = 100000\nbig - 1", 99999),
+            ("let x = 1\nx = x + 1\nx", 2),
+            ("let x = 10\nx += 5\nx -= 3\nx *= 2\nx /= 4\nx", 6),
+            ("1 == 1", 1),
+            ("1 != 1", 0),
+            ("3 > 2", 1),
+            ("2 > 3", 0),
+            ("2 < 3", 1),
+            ("3 >= 3", 1),
+            ("4 <= 3", 0),
+            ("let b = 2 * 3 == 6\nb + (1 < 0)", 1),
+            ("if 1 < 2 { 10 } else { 20 }", 10),
+            ("if 1 > 2 { 10 } else { 20 }", 20),
+            ("if 1 > 2 { 10 }", 0),
+            (
+                "let a = 3\nlet b = 7\nlet m = if a > b { a } else { b }\nm",
+                7,
+            ),
+            (
+                "let x = 2\nif x == 1 { 10 } else if x == 2 { 20 } else { 30 }",
+                20,
+            ),
+            (
+                "let x = 5\nif x == 1 { 10 } else if x == 2 { 20 } else { 30 }",
+                30,
+            ),
+            ("let x = 0\nif x { 1 } else { 2 }", 2),
+            ("let x = 1\nif x > 0 { x += 10 }\nx", 11),
+            (
+                "let x = 1\nif x > 0 {\n  let y = x * 3\n  y + 1\n} else {\n  0\n}",
+                4,
+            ),
+            ("1 + if 2 >= 2 { 5 } else { 6 } * 2", 11),
+            (
+                "let n = 5\nlet f = 1\nwhile n > 1 {\n  f *= n\n  n -= 1\n}\nf",
+                120,
+            ),
+            (
+                "let a = 1071\nlet b = 462\nwhile b != 0 {\n  let t = b\n  b = a - a / b * b\n  a = t\n}\na",
+                21,
+            ),
+            (
+                "let a = 0\nlet b = 1\nlet i = 0\nwhile 1 {\n  if i == 10 { break }\n  let t = a + b\n  a = b\n  b = t\n  i += 1\n}\na",
+                55,
+            ),
+            (
+                "let i = 0\nlet odd = 0\nwhile i < 10 {\n  i += 1\n  if i / 2 * 2 == i { continue }\n  odd += i\n}\nodd",
+                25,
+            ),
+            ("let i = 0\nwhile i > 0 { i += 1 }\ni", 0),
+            ("print(\"hello\\n\")\nprint(\"hello\\n\")\n7", 7),
+            ("fn add(a, b) {\n  return a + b\n}\nadd(2, 3) * 2", 10),
+            ("fn square(x) { x * x }\nsquare(square(3))", 81),
+            ("fn zero() { }\nzero() + 1", 1),
+            (
+                "fn fib(n) {\n  if n < 2 { return n }\n  fib(n - 1) + fib(n - 2)\n}\nfib(10)",
+                55,
+            ),
+            (
+                "let before = 7\nlet total = fact(5) + before\nfn fact(n) {\n  if n <= 1 { return 1 }\n  n * fact(n - 1)\n}\ntotal + before",
+                134,
+            ),
+            (
+                "fn gcd(a, b) {\n  while b != 0 {\n    let t = b\n    b = a - a / b * b\n    a = t\n  }\n  a\n}\ngcd(1071, 462)",
+                21,
+            ),
+            (
+                "fn is_even(n) { if n == 0 { 1 } else { is_odd(n - 1) } }\nfn is_odd(n) { if n == 0 { 0 } else { is_even(n - 1) } }\nis_even(10) * 10 + is_odd(7)",
+                11,
+            ),
+        ];
+        for (source, expected) in cases {
+            let result = run(source).unwrap();
+            assert_eq!(Some(Value::Int(expected)), result.value, "{}", source);
+            assert_eq!(ExitState::Halted { code: 0 }, result.exit);
+        }
+    }
+
+    #[test]
+    fn test_run_logical_operators() {
+        let cases = [
+            ("true", 1),
+            ("false", 0),
+            ("!true", 0),
+            ("!0", 1),
+            ("1 < 2 && 2 < 3", 1),
+            ("1 < 2 && 3 < 2", 0),
+            ("1 > 2 || 2 < 3", 1),
+            ("false || 1 > 2", 0),
+            ("true || false && false", 1),
+            ("(true || false) && false", 0),
+            ("!(1 < 2) || !(2 < 1)", 1),
+            (
+                "let b = 2 > 1 && !false
+b + 1",
+                2,
+            ),
+            (
+                "let x = 3
+if x > 0 && x < 5 { 1 } else { 2 }",
+                1,
+            ),
+            (
+                "let x = 7
+if x < 0 || x > 5 { 1 } else { 2 }",
+                1,
+            ),
+            (
+                "let x = 3
+if !(x < 0 || x > 5) { 1 } else { 2 }",
+                1,
+            ),
+            (
+                "let x = 3
+if x && !false { 1 } else { 2 }",
+                1,
+            ),
+            (
+                "let i = 0
+let n = 0
+while i < 10 && n < 4 {
+  i += 1
+  n += 2
+}
+i",
+                2,
+            ),
+            // The right side is not evaluated once the left side decides
+            (
+                "let x = 0
+false && if 1 { x = 5
+1 } else { 0 }
+x",
+                0,
+            ),
+            (
+                "let x = 0
+true || if 1 { x = 5
+1 } else { 0 }
+x",
+                0,
+            ),
+            (
+                "let x = 0
+true && if 1 { x = 5
+1 } else { 0 }
+x",
+                5,
+            ),
+            (
+                "let x = 0
+if false || if 1 { x = 5
+1 } else { 0 } { x += 1 }
+x",
+                6,
+            ),
+        ];
+        for (source, expected) in cases {
+            let result = run(source).unwrap();
+            assert_eq!(Some(Value::Int(expected)), result.value, "{}", source);
+            assert_eq!(ExitState::Halted { code: 0 }, result.exit);
+        }
+    }
+
+    #[test]
+    fn test_run_integer_operators() {
+        let cases = [
+            ("7 % 3", 1),
+            ("-7 % 3", -1),
+            ("7 % -3", 1),
+            ("2 * 7 % 4", 2),
+            ("2 ** 10", 1024),
+            ("2 ** 3 ** 2", 512),
+            ("(2 ** 3) ** 2", 64),
+            ("2 * 3 ** 2", 18),
+            ("3 ** 0", 1),
+            ("2 ** -1", 0),
+            ("1 ** -4", 1),
+            ("(0 - 1) ** -3", -1),
+            ("(0 - 1) ** -2", 1),
+            ("12 & 10", 8),
+            ("12 | 10", 14),
+            ("12 ^ 10", 6),
+            ("1 | 2 ^ 3 & 1", 3),
+            ("1 << 4", 16),
+            ("1 << 2 + 1", 8),
+            ("256 >> 4", 16),
+            ("-16 >> 2", -4),
+            ("-7 >> 1", -4),
+            ("5 >> -1", 5),
+            ("5 << 0", 5),
+            ("5 << 20", 5242880),
+            ("3 << 17", 393216),
+            ("1 << 31", -2147483648),
+            ("1 << 32", 0),
+            ("1 << 1000", 0),
+            ("-1 >> 40", -1),
+            ("1024 >> 10", 1),
+            ("0x7FFF_FFFF >> 30", 1),
+            ("let n = 3\n40 >> n", 5),
+            ("6 & 3 == 2", 1),
+            ("2147483647", 2147483647),
+            ("-2147483647 - 1", -2147483648),
+            ("-2147483648", -2147483648),
+            ("- -2147483648", -2147483648),
+            ("0x8000_0000", -2147483648),
+            ("0xFFFF_FFFF", -1),
+            ("0xFFFF_FFFF & 0xF0", 240),
+            ("let x = 10\nx % 4 + x >> 1", 6),
+            (
+                "let n = 3\nlet bits = 0\nwhile n > 0 {\n  bits += n & 1\n  n = n >> 1\n}\nbits",
+                2,
+            ),
+        ];
+        for (source, expected) in cases {
+            let result = run(source).unwrap();
+            assert_eq!(Some(Value::Int(expected)), result.value, "{}", source);
+            assert_eq!(ExitState::Halted { code: 0 }, result.exit);
+        }
+
+        assert_eq!(Some(Value::Float(2.25)), run("1.5 ** 2").unwrap().value);
+        assert_eq!(Some(Value::Float(0.25)), run("2.0 ** -2").unwrap().value);
+        assert_eq!(
+            Some(Value::Float(-2147483648.0)),
+            run("let x: f64 = -2147483648\nx").unwrap().value
+        );
+        assert!(matches!(
+            run("0 ** -1").unwrap().exit,
+            ExitState::Crashed { .. }
+        ));
+        assert_eq!(
+            Some(Value::Float(8.0)),
+            run("let x: f64 = 2 ** 3\nx").unwrap().value
+        );
+    }
+
+    #[test]
+    fn test_run_with_spilled_bindings() {
+        let mut source: String = (1..=30).map(|i| format!("let v{} = {}\n", i, i)).collect();
+        let sum: Vec<String> = (1..=30).map(|i| format!("v{}", i)).collect();
+        source.push_str(&sum.join(" + "));
+        assert_eq!(Some(Value::Int(465)), run(&source).unwrap().value);
+
+        // Assignments have to write back to the spill slot
+        source.push_str("\nv30 += 100\nv29 = v30 * 2\nv29 + v30");
+        assert_eq!(Some(Value::Int(390)), run(&source).unwrap().value);
+    }
+
+    #[test]
+    fn test_run_with_comments() {
+        let cases = [
+            ("1 + 2 // three", 3),
+            ("// leading\n/* and */ 4", 4),
+            ("8 / /* two */ 2", 4),
+            ("8 /* / 3 */ / 2 // / 4", 4),
+            ("/* outer /* inner */ still a comment */ 5", 5),
+            ("let /* name */ x = 6 // six\nx", 6),
+            (
+                "fn add(a /* first */, b // second\n) {\n  // sum\n  return a + b\n}\nadd(3, 4)",
+                7,
+            ),
+            ("let x = 1\nwhile x < 8 { // double\n  x = x * 2\n}\nx", 8),
+        ];
+        for (source, expected) in cases {
+            let result = run(source).unwrap();
+            assert_eq!(Some(Value::Int(expected)), result.value, "{}", source);
+        }
+        let diagnostics = parse_program("1\n+ /* unclosed /* */\n2").unwrap_err();
+        assert!(diagnostics.to_string().starts_with(
+            "error: unterminated block comment at 2:3\n  |\n2 | + /* unclosed /* */\n  |   ^"
+        ));
+    }
+
+    #[test]
+    fn test_run_with_statement_separators() {
+        let cases = [
+            ("1; 2; 3", 3),
+            ("let x = 2; x * 3;", 6),
+            // A line that starts with a sign is a statement of its own...
+            ("let x = 5\n-x", -5),
+            ("let x = 5\nlet y = x\n+ 1\ny", 5),
+            // ...while an operator at the end of a line goes on with the next
+            ("let x = 5 -\n  1\nx", 4),
+            ("{ let y = 2; y * 3 }", 6),
+            ("let x = 1\n{\n  x += 1\n}\nx", 2),
+            ("fn f(a) {\n  return\n  a\n}\nf(1)\n7", 7),
+            ("1;; 2", 2),
+            ("1 /* a */ - 2", -1),
+            ("1 /* a\n b */ - 2", -2),
+            // Inside parentheses nothing ends, but in a block in them it does
+            ("(1\n- 2)", -1),
+            ("fn f(a, b) { a - b }\nf(5\n- 1,\n2)", 2),
+            ("(if 1 { 3\n-1 } else { 0 }\n+ 1)", 0),
+        ];
+        for (source, expected) in cases {
+            let result = run(source).unwrap();
+            assert_eq!(Some(Value::Int(expected)), result.value, "{}", source);
+        }
+        // A program can be empty
+        for source in ["", "// only a comment\n", "\n\n;"] {
+            let result = run(source).unwrap();
+            assert_eq!(ExitState::Halted { code: 0 }, result.exit, "{:?}", source);
+        }
+        for source in ["1 2", "let x = 1 let y = 2", "(1\n2)"] {
+            assert!(
+                matches!(parse_errors(source)[..], [CompileError::Syntax(_)]),
+                "{}",
+                source
+            );
+        }
+    }
+
+    #[test]
+    fn test_run_floats() {
+        let cases = [
+            ("1.5 + 2.25", 3.75),
+            ("12.0625", 12.0625),
+            ("-0.001 * 2.0", -0.002),
+            ("-(1.5 + 1.0)", -2.5),
+            (".5 + 1e1", 10.5),
+            ("2.5e-1", 0.25),
+            ("1e-3 * 1_000.0", 1.0),
+            ("10.0 / 4.0 - 0.5", 2.0),
+            ("123456.789 * 1000.0", 123456789.0),
+            ("let x = 1.5\nx *= 2.0\nx + 0.25", 3.25),
+            ("let x = 2.5\nif x > 2.0 { x } else { 0.0 }", 2.5),
+            ("let x: f64 = 3\nx / 2.0", 1.5),
+            ("fn half(x: f64) -> f64 { x / 2 }\nhalf(5)", 2.5),
+            (
+                "fn area(w: f64, h: f64) -> f64 {\n  if w < 0.0 { return 0 }\n  w * h\n}\nlet a = area(1.5, 4)\na + area(-1, 2)",
+                6.0,
+            ),
+            (
+                "let x = 0.0\nlet i = 0\nwhile i < 4 {\n  x += 0.5\n  i += 1\n}\nx",
+                2.0,
+            ),
+        ];
+        for (source, expected) in cases {
+            let result = run(source).unwrap();
+            assert_eq!(Some(Value::Float(expected)), result.value, "{}", source);
+            assert_eq!(ExitState::Halted { code: 0 }, result.exit);
+        }
+
+        // Comparisons of floats give integers
+        assert_eq!(Some(Value::Int(1)), run("1.5 < 2.5").unwrap().value);
+        assert_eq!(Some(Value::Int(0)), run("0.1 == 0.2").unwrap().value);
+    }
+
+    #[test]
+    fn test_mixed_operands_are_rejected() {
+        for (source, line, column) in [
+            ("1 + 2.0", 1, 1),
+            ("let x = 1\nx = 2.5", 2, 1),
+            ("let x = 1.50\nif x > 1.0 {\n  x\n} else {\n  1\n}", 4, 8),
+            ("fn f(a, b) { a }\nf(1, (0.5 + 0.5))", 2, 6),
+            ("2 /* a + b */ * 3 + 2.0", 1, 1),
+            ("let x = 1; x + 2.0", 1, 12),
+            ("1 + 2\n1 + 2.0", 2, 1),
+        ] {
+            match codegen(source).err() {
+                Some(CompileError::Diagnostics(diagnostics)) => match &diagnostics.errors[..] {
+                    [CompileError::Type { span, .. }] => {
+                        assert_eq!((line, column), (span.line, span.column), "{}", source)
+                    }
+                    other => panic!("expected a type error for {}, got {:?}", source, other),
+                },
+                other => panic!("expected a type error for {}, got {:?}", source, other),
+            }
+        }
+        assert_eq!(
+            "type error at 1:1: mismatched types: cannot apply `+` to i32 and f64",
+            codegen("1 + 2.0").err().unwrap().to_string()
+        );
+
+        // Every type error is reported, not only the first
+        assert_eq!(
+            "type error at 1:9: mismatched types: cannot apply `+` to i32 and f64\n\
+             type error at 2:1: mismatched types: cannot apply `*` to f64 and i32",
+            codegen("let x = 1 + 2.0\n1.5 * 2")
+                .err()
+                .unwrap()
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn test_codegen_stops_before_assembling() {
+        let compiler = codegen("1+2").unwrap();
+        let assembly = compiler.assembly();
+        assert!(assembly.contains("LOAD $0 #1"));
+        assert!(assembly.contains("ADD $0 $1 $2"));
+    }
+
+    #[test]
+    fn test_hex_dump() {
+        let dump = hex_dump(&[0x2d, 0x32, 0x31, 0x2d, 0x00, 0xff]);
+        assert_eq!(
+            "00000000: 2d 32 31 2d 00 ff                                |-21-..|\n",
+            dump
+        );
+    }
+
+    /// The errors that keep `source` from parsing
+    fn parse_errors(source: &str) -> Vec<CompileError> {
+        match parse(source) {
+            Err(CompileError::Diagnostics(diagnostics)) => diagnostics.errors,
+            other => panic!("expected {:?} not to parse, got {:?}", source, other),
+        }
+    }
+
+    #[test]
+    fn test_parse_error_location() {
+        match parse_errors("\n  )").remove(0) {
+            CompileError::Syntax(error) => {
+                assert_eq!(2, error.span.line);
+                assert_eq!(3, error.span.column);
+                assert_eq!("expected factor", error.message);
+                assert_eq!(
+                    "parse error at 2:3: expected factor",
+                    CompileError::Syntax(error).to_string()
+                );
+            }
+            other => panic!("expected a parse error, got {:?}", other),
+        }
+        // What is left over after the last statement is an error too
+        assert!(matches!(
+            parse_errors("let x = 1\nx + 1 )")[..],
+            [CompileError::Syntax(ref error)] if (error.span.line, error.span.column) == (2, 7)
+        ));
+    }
+
+    #[test]
+    fn test_parse_reports_every_error() {
+        let error = parse("let x = )\nx\n1 @ 2\nlet y = 0x1_0000_0000_0000_0000").unwrap_err();
+        assert_eq!(
+            "parse error at 1:9: expected factor\n\
+             parse error at 3:3: expected end of statement\n\
+             parse error at 4:9: literal `0x1_0000_0000_0000_0000` is out of range for i32",
+            error.to_string()
+        );
+    }
+
+    #[test]
+    fn test_out_of_range_literal_has_a_span() {
+        let source = "let x = 1\nlet y = 0xFFFF_FFFF_FFFF_FFFF + x";
+        let error = parse_errors(source).remove(0);
+        assert_eq!(
+            CompileError::LiteralOutOfRange {
+                literal: "0xFFFF_FFFF_FFFF_FFFF".to_string(),
+                ty: Type::Int,
+                span: Span {
+                    offset: 18,
+                    length: 21,
+                    line: 2,
+                    column: 9,
+                },
+            },
+            error
+        );
+        assert_eq!(
+            "parse error at 2:9: literal `0xFFFF_FFFF_FFFF_FFFF` is out of range for i32",
+            error.to_string()
+        );
+
+        match parse_errors("2147483647 + 2147483648").remove(0) {
+            CompileError::LiteralOutOfRange { literal, span, .. } => {
+                assert_eq!("2147483648", literal);
+                assert_eq!((13, 10), (span.offset, span.length));
+            }
+            other => panic!("expected an out of range literal, got {:?}", other),
+        }
+
+        match parse_errors("1.5 * 1e999").remove(0) {
+            CompileError::LiteralOutOfRange { ty, span, .. } => {
+                assert_eq!(Type::Float, ty);
+                assert_eq!((6, 5), (span.offset, span.length));
+            }
+            other => panic!("expected an out of range literal, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_break_outside_loop_points_at_the_keyword() {
+        let source = "let x = 1\nif x { 2 }\nif x {\n  break\n}";
+        match parse_errors(source).remove(0) {
+            CompileError::Syntax(error) => {
+                assert_eq!("`break` outside of a loop", error.message);
+                assert_eq!(
+                    (30, 5, 4, 3),
+                    (
+                        error.span.offset,
+                        error.span.length,
+                        error.span.line,
+                        error.span.column
+                    )
+                );
+                assert_eq!("  break", error.source_line);
+                assert!(error.to_string().starts_with(
+                    "error: `break` outside of a loop at 4:3\n  |\n4 |   break\n  |   ^"
+                ));
+            }
+            other => panic!("expected a syntax error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_return_outside_function_points_at_the_keyword() {
+        assert_eq!(
+            "parse error at 2:3: `return` outside of a function",
+            parse("let x = 1\n  return x").unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn test_every_misplaced_control_flow_is_reported() {
+        let error =
+            parse("break\nlet x = )\nwhile 1 { continue }\ncontinue; return 1").unwrap_err();
+        assert_eq!(
+            "parse error at 1:1: `break` outside of a loop\n\
+             parse error at 2:9: expected factor\n\
+             parse error at 4:1: `continue` outside of a loop\n\
+             parse error at 4:11: `return` outside of a function",
+            error.to_string()
+        );
+    }
+}
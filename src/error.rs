@@ -0,0 +1,465 @@
+use std::fmt;
+
+use nom::{
+    error::{ErrorKind, VerboseError, VerboseErrorKind},
+    InputTake,
+};
+
+use crate::{
+    span::{Input, Span},
+    token::Token,
+    type_checker::{Type, TypeError},
+};
+
+/// Everything that can go wrong while turning source text into bytecode.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CompileError {
+    /// The source could not be parsed. `line` and `column` are 1-based and
+    /// point at the first character the parser could not make sense of.
+    Parse {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    /// The source does not follow the grammar. The `ParseError` says where
+    /// parsing stopped and what was expected there.
+    Syntax(ParseError),
+    /// Everything that kept the source from parsing, as found in a single
+    /// pass over it, or from type checking
+    Diagnostics(Diagnostics),
+    /// The program does not type check. `span` is the node the error is
+    /// about, like the whole of `1 + 2.0` for its mismatched operands.
+    Type { span: Span, error: TypeError },
+    /// A numeric literal is too large for its type, e.g. a decimal integer
+    /// literal above `i32::MAX` or a hexadecimal one above `u32::MAX`
+    LiteralOutOfRange {
+        literal: String,
+        ty: Type,
+        span: Span,
+    },
+    /// The generated assembly was rejected by the lrvm `Assembler`
+    Assemble { message: String },
+    /// An identifier was used without a `let` binding in scope
+    UndefinedVariable { name: String },
+    /// A `let` tried to bind a name that is already bound
+    DuplicateBinding { name: String },
+    /// An expression needed more temporaries than there are registers
+    OutOfRegisters,
+    /// The left side of an assignment is not something that can be assigned
+    /// to, e.g. `1 = 2`
+    InvalidAssignmentTarget { target: String },
+    /// A `break` or `continue` appeared outside of any loop
+    LoopControlOutsideLoop { keyword: String },
+    /// A `return` appeared outside of any function
+    ReturnOutsideFunction,
+    /// A call named a function that has not been defined
+    UndefinedFunction { name: String },
+    /// A function was defined twice
+    DuplicateFunction { name: String },
+    /// A call passed a different number of arguments than the function has
+    /// parameters
+    ArgumentCountMismatch {
+        name: String,
+        expected: usize,
+        found: usize,
+    },
+    /// A function has more parameters than there are argument registers
+    TooManyParameters { name: String, limit: usize },
+    /// A string literal was used somewhere other than as the argument of
+    /// `print`
+    UnexpectedString { value: String },
+    /// An operation was given an integer and a float. lrvm keeps the two in
+    /// separate registers and cannot convert between them.
+    MixedOperands { operation: String },
+    /// An operation that only works on integers, like `%` or `&`, was given
+    /// a float
+    IntegerOnly { operation: String },
+    /// A function was called while a float value was kept in a register
+    /// the function may overwrite. lrvm cannot push float registers, so the
+    /// value could not be saved across the call.
+    FloatLiveAcrossCall { name: String },
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompileError::Parse {
+                line,
+                column,
+                message,
+            } => write!(f, "parse error at {}:{}: {}", line, column, message),
+            CompileError::Syntax(error) => write!(
+                f,
+                "parse error at {}:{}: {}",
+                error.span.line, error.span.column, error.message
+            ),
+            CompileError::Diagnostics(diagnostics) => {
+                for (i, error) in diagnostics.errors.iter().enumerate() {
+                    if i > 0 {
+                        writeln!(f)?;
+                    }
+                    write!(f, "{}", error)?;
+                }
+                Ok(())
+            }
+            CompileError::Type { span, error } => {
+                write!(f, "type error at {}:{}: {}", span.line, span.column, error)
+            }
+            CompileError::LiteralOutOfRange { literal, ty, span } => write!(
+                f,
+                "parse error at {}:{}: literal `{}` is out of range for {}",
+                span.line, span.column, literal, ty
+            ),
+            CompileError::Assemble { message } => write!(f, "assemble error: {}", message),
+            CompileError::UndefinedVariable { name } => {
+                write!(f, "cannot find variable `{}` in this scope", name)
+            }
+            CompileError::DuplicateBinding { name } => {
+                write!(f, "variable `{}` is already defined", name)
+            }
+            CompileError::OutOfRegisters => {
+                write!(f, "expression is too complex: ran out of registers")
+            }
+            CompileError::InvalidAssignmentTarget { target } => {
+                write!(
+                    f,
+                    "cannot assign to `{}`: only variables can be assigned to",
+                    target
+                )
+            }
+            CompileError::LoopControlOutsideLoop { keyword } => {
+                write!(f, "`{}` outside of a loop", keyword)
+            }
+            CompileError::ReturnOutsideFunction => write!(f, "`return` outside of a function"),
+            CompileError::UndefinedFunction { name } => {
+                write!(f, "cannot find function `{}`", name)
+            }
+            CompileError::DuplicateFunction { name } => {
+                write!(f, "function `{}` is already defined", name)
+            }
+            CompileError::ArgumentCountMismatch {
+                name,
+                expected,
+                found,
+            } => write!(
+                f,
+                "function `{}` takes {} argument(s) but {} were given",
+                name, expected, found
+            ),
+            CompileError::TooManyParameters { name, limit } => {
+                write!(f, "function `{}` has more than {} parameters", name, limit)
+            }
+            CompileError::UnexpectedString { value } => write!(
+                f,
+                "string literal {:?} can only be used as the argument of `print`",
+                value
+            ),
+            CompileError::MixedOperands { operation } => write!(
+                f,
+                "`{}` cannot mix integers and floats: lrvm has no conversion between them",
+                operation
+            ),
+            CompileError::IntegerOnly { operation } => {
+                write!(f, "`{}` only works on integers", operation)
+            }
+            CompileError::FloatLiveAcrossCall { name } => write!(
+                f,
+                "cannot call `{}` while a float value is live: lrvm cannot save float registers",
+                name
+            ),
+        }
+    }
+}
+
+impl std::error::Error for CompileError {}
+
+impl From<VerboseError<Input<'_>>> for CompileError {
+    /// Literals that are out of range have errors of their own. Anything
+    /// else is a syntax error.
+    fn from(error: VerboseError<Input>) -> Self {
+        match error.errors.first() {
+            Some(&(
+                literal,
+                VerboseErrorKind::Nom(kind @ (ErrorKind::TooLarge | ErrorKind::Float)),
+            )) => CompileError::LiteralOutOfRange {
+                literal: literal.to_string(),
+                ty: if kind == ErrorKind::Float {
+                    Type::Float
+                } else {
+                    Type::Int
+                },
+                span: Span::of(literal),
+            },
+            _ => CompileError::Syntax(ParseError::from(error)),
+        }
+    }
+}
+
+/// Everything found wrong with a source while parsing it, or while type
+/// checking it once it parsed, in the order it appears in the source, along
+/// with what could be parsed of it. There is always at least one error.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostics {
+    pub errors: Vec<CompileError>,
+    /// The program with a `Token::Error` in place of every statement that
+    /// did not parse
+    pub program: Token,
+}
+
+impl fmt::Display for Diagnostics {
+    /// Syntax errors are shown with their line of the source, anything else
+    /// on a line of its own
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, error) in self.errors.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            match error {
+                CompileError::Syntax(error) => write!(f, "{}", error)?,
+                error => write!(f, "error: {}", error)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Diagnostics {}
+
+/// A syntax error, usually made from the chain of contexts nom went through
+/// before it failed. As text it reads
+///
+/// ```text
+/// error: expected factor at 3:7
+///   |
+/// 3 | x = 1 *
+///   |        ^
+///   = in program_parser > in statement_parser > ... > in factor_parser
+/// ```
+///
+/// # Example
+///
+/// ```
+/// use lrvmism::error::ParseError;
+/// use lrvmism::program_parsers::program_parser;
+/// let error = match program_parser("\n  )".into()) {
+///     Err(nom::Err::Error(e)) => ParseError::from(e),
+///     other => panic!("expected an error, got {:?}", other),
+/// };
+/// assert_eq!("expected factor", error.message);
+/// assert_eq!((2, 3), (error.span.line, error.span.column));
+/// assert_eq!(Some(&"factor_parser"), error.context.last());
+/// assert!(error.to_string().starts_with("error: expected factor at 2:3\n"));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    /// What is wrong, like `expected factor` when the parser was looking
+    /// for a factor, or `unterminated block comment`
+    pub message: String,
+    /// Where parsing stopped, which is an empty span between two characters,
+    /// or what the error is about, like a misplaced `break`
+    pub span: Span,
+    /// The contexts the parser was in when it failed, outermost first
+    pub context: Vec<&'static str>,
+    /// The line of the source `span` is on, without its line ending
+    pub source_line: String,
+}
+
+impl From<VerboseError<Input<'_>>> for ParseError {
+    fn from(error: VerboseError<Input>) -> Self {
+        let Some(&(input, ref kind)) = error.errors.first() else {
+            return ParseError {
+                message: "expected input".to_string(),
+                span: Span::default(),
+                context: vec![],
+                source_line: String::new(),
+            };
+        };
+        let context: Vec<&'static str> = error
+            .errors
+            .iter()
+            .rev()
+            .filter_map(|(_, kind)| match kind {
+                VerboseErrorKind::Context(name) => Some(*name),
+                _ => None,
+            })
+            .collect();
+        // A missing character is only worth naming when the parser got past
+        // the start of what it was parsing, like the `)` of `(1 + 2`. It is
+        // then expected right after what came before it, not after the
+        // whitespace that followed. When nothing matched, e.g. with the `(`
+        // that `factor_parser` tries last, the context says better what was
+        // expected.
+        let outer = error
+            .errors
+            .get(1)
+            .map(|&(outer, _)| outer)
+            .filter(|outer| outer.location_offset() < input.location_offset());
+        let (message, input) = match (kind, outer, context.last()) {
+            (VerboseErrorKind::Char(c), Some(outer), _) => {
+                let consumed =
+                    &outer.fragment()[..input.location_offset() - outer.location_offset()];
+                (
+                    format!("expected '{}'", c),
+                    outer.take_split(consumed.trim_end().len()).0,
+                )
+            }
+            // The comment parser gives up at the `/*` that is never closed
+            (VerboseErrorKind::Nom(ErrorKind::TakeUntil), _, _) => {
+                ("unterminated block comment".to_string(), input)
+            }
+            (VerboseErrorKind::Nom(ErrorKind::Eof), _, _) => {
+                ("expected end of input".to_string(), input)
+            }
+            (_, _, Some(name)) => (format!("expected {}", describe(name)), input),
+            (VerboseErrorKind::Char(c), None, None) => (format!("expected '{}'", c), input),
+            (_, _, None) => ("expected input".to_string(), input),
+        };
+        let source_line = String::from_utf8_lossy(input.get_line_beginning());
+        ParseError {
+            message,
+            span: Span::between(input, input),
+            context,
+            source_line: source_line.trim_end_matches('\r').to_string(),
+        }
+    }
+}
+
+impl ParseError {
+    /// A syntax error about the part of `source` that `span` covers, found
+    /// after parsing rather than by a parser, so without a context
+    pub fn at(source: &str, span: Span, message: String) -> Self {
+        let start = source[..span.offset].rfind('\n').map_or(0, |i| i + 1);
+        let end = source[span.offset..]
+            .find('\n')
+            .map_or(source.len(), |i| span.offset + i);
+        ParseError {
+            message,
+            span,
+            context: vec![],
+            source_line: source[start..end].trim_end_matches('\r').to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let line = self.span.line.to_string();
+        let gutter = " ".repeat(line.len());
+        // Tabs are kept so that the caret lines up with the source line
+        let indent: String = self
+            .source_line
+            .chars()
+            .take(self.span.column.saturating_sub(1))
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        writeln!(
+            f,
+            "error: {} at {}:{}",
+            self.message, self.span.line, self.span.column
+        )?;
+        writeln!(f, "{} |", gutter)?;
+        writeln!(f, "{} | {}", line, self.source_line)?;
+        write!(f, "{} | {}^", gutter, indent)?;
+        if !self.context.is_empty() {
+            let context: Vec<String> = self
+                .context
+                .iter()
+                .map(|name| format!("in {}", name))
+                .collect();
+            write!(f, "\n{} = {}", gutter, context.join(" > "))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// How a parser context reads in a message: `factor_parser` is a `factor`
+/// and `comparison_operator` a `comparison operator`
+fn describe(context: &str) -> String {
+    context.trim_end_matches("_parser").replace('_', " ")
+}
+
+/// Converts the unparsed `remaining` tail of `source` into a 1-based
+/// `(line, column)` pair.
+pub fn location(source: &str, remaining: &str) -> (usize, usize) {
+    let offset = source.len() - remaining.len();
+    let consumed = &source[..offset];
+    let line = consumed.matches('\n').count() + 1;
+    let column = match consumed.rfind('\n') {
+        Some(i) => consumed[i + 1..].chars().count() + 1,
+        None => consumed.chars().count() + 1,
+    };
+    (line, column)
+}
+
+/// The length of the string literal `text` starts with, quotes included. A
+/// literal that is never closed runs to the end of `text`.
+pub fn string_literal_len(text: &str) -> usize {
+    let mut escaped = false;
+    for (i, c) in text.char_indices().skip(1) {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '"' => return i + 1,
+            _ => {}
+        }
+    }
+    text.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::program_parsers::program_parser;
+
+    use super::{location, ParseError};
+
+    fn parse_error(source: &str) -> ParseError {
+        match program_parser(source.into()) {
+            Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => ParseError::from(e),
+            other => panic!("expected {:?} not to parse, got {:?}", source, other),
+        }
+    }
+
+    #[test]
+    fn test_location() {
+        let source = "1+2\n3*4\n  5";
+        assert_eq!((1, 1), location(source, source));
+        assert_eq!((2, 1), location(source, &source[4..]));
+        assert_eq!((3, 3), location(source, &source[10..]));
+        assert_eq!((3, 4), location(source, ""));
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let error = parse_error("// sum\n(1 + 2\n");
+        assert_eq!("expected ')'", error.message);
+        assert_eq!(
+            (2, 7, 0),
+            (error.span.line, error.span.column, error.span.length)
+        );
+        assert_eq!("(1 + 2", error.source_line);
+        assert_eq!(["program_parser", "statement_parser"], error.context[..2]);
+        assert_eq!(Some(&"factor_parser"), error.context.last());
+        let rendered = error.to_string();
+        let lines: Vec<&str> = rendered.lines().collect();
+        assert_eq!(
+            [
+                "error: expected ')' at 2:7",
+                "  |",
+                "2 | (1 + 2",
+                "  |       ^",
+            ],
+            lines[..4]
+        );
+        assert!(lines[4].starts_with("  = in program_parser > in statement_parser > "));
+        assert!(lines[4].ends_with(" > in power_parser > in factor_parser"));
+
+        // The caret keeps the tabs of the line so that it stays under the
+        // offending character
+        let error = parse_error("\t\t)\r\n");
+        assert_eq!("expected factor", error.message);
+        assert_eq!("\t\t)", error.source_line);
+        assert!(error.to_string().contains("\n1 | \t\t)\n  | \t\t^\n"));
+    }
+}
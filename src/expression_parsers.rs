@@ -1,40 +1,330 @@
 use nom::{
     branch::alt,
-    character::complete::{line_ending, multispace0},
-    combinator::{eof, map},
+    combinator::{map, opt},
     error::context,
     multi::many0,
     sequence::{preceded, terminated, tuple},
-    IResult,
 };
 
 use crate::{
-    operator_parsers::{addition_operator, substraction_operator},
+    operator_parsers::{
+        addition_operator, and_operator, bit_and_operator, bit_or_operator, bit_xor_operator,
+        comparison_operator, infix, or_operator, shift_left_operator, shift_right_operator,
+        substraction_operator,
+    },
+    span::{Input, ParseResult},
     term_parsers::term_parser,
     token::Token,
+    trivia_parsers::{trailing_trivia, trivia},
 };
 
-pub fn expression_parser(input: &str) -> IResult<&str, Token> {
+pub fn expression_parser(input: Input) -> ParseResult<Token> {
     context(
         "expression_parser",
         preceded(
-            multispace0,
+            trivia,
             terminated(
                 map(
                     tuple((
                         term_parser,
                         many0(tuple((
-                            alt((addition_operator, substraction_operator)),
+                            infix(alt((addition_operator, substraction_operator))),
                             term_parser,
                         ))),
                     )),
                     |(left, right)| Token::Expression {
+                        span: left.span_to(right.last().map_or(&left, |(_, last)| last)),
                         left: Box::new(left),
                         right,
                     },
                 ),
-                alt((multispace0, line_ending, eof)),
+                trailing_trivia,
             ),
         ),
     )(input)
 }
+
+/// Parser for the integer operators above `expression_parser`. From the
+/// tightest-binding to the loosest these are the shifts `<<` and `>>`, then
+/// `&`, `^` and `|`, all left-associative, as in Rust. Without any of them
+/// this is just the `Expression`.
+///
+/// # Example
+///
+/// ```
+/// use lrvmism::expression_parsers::bitwise_parser;
+/// use lrvmism::token::Token;
+/// let (_reminder, token) = bitwise_parser("a | b & 1 << 2".into()).unwrap();
+/// match token {
+///     Token::Bitwise { operator, right, .. } => {
+///         assert_eq!(Token::BitOrOperator, *operator);
+///         assert_eq!("b & 1 << 2", right.to_string());
+///     }
+///     other => panic!("expected a bitwise operation, got {:?}", other),
+/// }
+/// ```
+pub fn bitwise_parser(input: Input) -> ParseResult<Token> {
+    context(
+        "bitwise_parser",
+        map(
+            tuple((
+                bit_xor_parser,
+                many0(tuple((infix(bit_or_operator), bit_xor_parser))),
+            )),
+            |(left, right)| fold_binary(left, right, bitwise),
+        ),
+    )(input)
+}
+
+fn bit_xor_parser(input: Input) -> ParseResult<Token> {
+    context(
+        "bit_xor_parser",
+        map(
+            tuple((
+                bit_and_parser,
+                many0(tuple((infix(bit_xor_operator), bit_and_parser))),
+            )),
+            |(left, right)| fold_binary(left, right, bitwise),
+        ),
+    )(input)
+}
+
+fn bit_and_parser(input: Input) -> ParseResult<Token> {
+    context(
+        "bit_and_parser",
+        map(
+            tuple((
+                shift_parser,
+                many0(tuple((infix(bit_and_operator), shift_parser))),
+            )),
+            |(left, right)| fold_binary(left, right, bitwise),
+        ),
+    )(input)
+}
+
+fn shift_parser(input: Input) -> ParseResult<Token> {
+    context(
+        "shift_parser",
+        map(
+            tuple((
+                expression_parser,
+                many0(tuple((
+                    infix(alt((shift_left_operator, shift_right_operator))),
+                    expression_parser,
+                ))),
+            )),
+            |(left, right)| fold_binary(left, right, bitwise),
+        ),
+    )(input)
+}
+
+/// Parser for a comparison, the level above `bitwise_parser`: a bitwise
+/// operation optionally compared with a second one, e.g. `a + 1 < b`.
+/// Without a comparison operator this is just the operand.
+///
+/// # Example
+///
+/// ```
+/// use lrvmism::expression_parsers::comparison_parser;
+/// use lrvmism::token::Token;
+/// let (_reminder, token) = comparison_parser("1 + 2 <= 3".into()).unwrap();
+/// assert!(matches!(token, Token::Comparison { .. }));
+/// let (_reminder, token) = comparison_parser("1 + 2".into()).unwrap();
+/// assert!(matches!(token, Token::Expression { .. }));
+/// ```
+pub fn comparison_parser(input: Input) -> ParseResult<Token> {
+    context(
+        "comparison_parser",
+        map(
+            tuple((
+                bitwise_parser,
+                opt(tuple((infix(comparison_operator), bitwise_parser))),
+            )),
+            |(left, right)| match right {
+                Some((operator, right)) => Token::Comparison {
+                    span: left.span_to(&right),
+                    left: Box::new(left),
+                    operator: Box::new(operator),
+                    right: Box::new(right),
+                },
+                None => left,
+            },
+        ),
+    )(input)
+}
+
+/// Parser for the logical operators, the loosest-binding level of an
+/// expression: comparisons joined by `&&`, which binds tighter, and `||`,
+/// both left-associative. Without a logical operator this is just the
+/// comparison.
+///
+/// # Example
+///
+/// ```
+/// use lrvmism::expression_parsers::logical_parser;
+/// use lrvmism::token::Token;
+/// let (_reminder, token) = logical_parser("a < 1 || b && c".into()).unwrap();
+/// match token {
+///     Token::Logical { operator, right, .. } => {
+///         assert_eq!(Token::OrOperator, *operator);
+///         assert_eq!("b && c", right.to_string());
+///     }
+///     other => panic!("expected a logical operation, got {:?}", other),
+/// }
+/// ```
+pub fn logical_parser(input: Input) -> ParseResult<Token> {
+    context(
+        "logical_parser",
+        map(
+            tuple((and_parser, many0(tuple((infix(or_operator), and_parser))))),
+            |(left, right)| fold_binary(left, right, logical),
+        ),
+    )(input)
+}
+
+/// Parser for comparisons joined by `&&`
+fn and_parser(input: Input) -> ParseResult<Token> {
+    context(
+        "and_parser",
+        map(
+            tuple((
+                comparison_parser,
+                many0(tuple((infix(and_operator), comparison_parser))),
+            )),
+            |(left, right)| fold_binary(left, right, logical),
+        ),
+    )(input)
+}
+
+/// Chains `left` and the operator and operand pairs after it into nested
+/// tokens made by `make`, from the left
+fn fold_binary(
+    left: Token,
+    right: Vec<(Token, Token)>,
+    make: fn(Token, Token, Token) -> Token,
+) -> Token {
+    right
+        .into_iter()
+        .fold(left, |left, (operator, right)| make(left, operator, right))
+}
+
+fn logical(left: Token, operator: Token, right: Token) -> Token {
+    Token::Logical {
+        span: left.span_to(&right),
+        left: Box::new(left),
+        operator: Box::new(operator),
+        right: Box::new(right),
+    }
+}
+
+fn bitwise(left: Token, operator: Token, right: Token) -> Token {
+    Token::Bitwise {
+        span: left.span_to(&right),
+        left: Box::new(left),
+        operator: Box::new(operator),
+        right: Box::new(right),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::token::Token;
+
+    use super::{bitwise_parser, comparison_parser, logical_parser};
+
+    #[test]
+    fn test_comparison_parser() {
+        for (input, operator) in [
+            ("a == b", Token::EqualOperator),
+            ("a != b", Token::NotEqualOperator),
+            ("a > b", Token::GreaterThanOperator),
+            ("a < b", Token::LessThanOperator),
+            ("a >= b", Token::GreaterThanOrEqualOperator),
+            ("a <= b", Token::LessThanOrEqualOperator),
+        ] {
+            let result = comparison_parser(input.into());
+            assert!(result.is_ok(), "{}", input);
+            let (r, token) = result.unwrap();
+            assert!(r.is_empty());
+            match token {
+                Token::Comparison {
+                    operator: parsed, ..
+                } => assert_eq!(operator, *parsed),
+                other => panic!("expected a comparison, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_comparison_binds_looser_than_arithmetic() {
+        let (_, token) = comparison_parser("1 + 2 * 3 > 4 - 5".into()).unwrap();
+        match token {
+            Token::Comparison { left, right, .. } => {
+                assert_eq!("1 + 2 * 3", left.to_string());
+                assert_eq!("4 - 5", right.to_string());
+            }
+            other => panic!("expected a comparison, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_logical_parser() {
+        for (input, expected) in [
+            ("a && b && c", "a && b && c"),
+            ("a || b && c", "a || b && c"),
+            ("(a || b) && c", "(a || b) && c"),
+            ("a < 1 || !b", "a < 1 || !b"),
+        ] {
+            let (r, token) = logical_parser(input.into()).unwrap();
+            assert!(r.is_empty(), "{}", input);
+            assert!(matches!(token, Token::Logical { .. }), "{}", input);
+            assert_eq!(expected, token.to_string());
+        }
+
+        // `&&` binds tighter than `||`, and both are left-associative
+        let (_, token) = logical_parser("a || b && c || d".into()).unwrap();
+        match token {
+            Token::Logical { left, right, .. } => {
+                assert_eq!("a || b && c", left.to_string());
+                assert_eq!("d", right.to_string());
+            }
+            other => panic!("expected a logical operation, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_bitwise_parser() {
+        // Shifts bind tighter than `&`, `&` than `^` and `^` than `|`
+        let (r, token) = bitwise_parser("a ^ b | c & d >> 1 << 2".into()).unwrap();
+        assert!(r.is_empty());
+        match token {
+            Token::Bitwise {
+                left,
+                operator,
+                right,
+                ..
+            } => {
+                assert_eq!("a ^ b", left.to_string());
+                assert_eq!(Token::BitOrOperator, *operator);
+                match *right {
+                    Token::Bitwise { left, right, .. } => {
+                        assert_eq!("c", left.to_string());
+                        assert_eq!("d >> 1 << 2", right.to_string());
+                    }
+                    other => panic!("expected a bitwise operation, got {:?}", other),
+                }
+            }
+            other => panic!("expected a bitwise operation, got {:?}", other),
+        }
+
+        // Arithmetic binds tighter than the shifts, comparisons looser
+        let (_, token) = comparison_parser("1 + 2 << 3 == 24".into()).unwrap();
+        match token {
+            Token::Comparison { left, .. } => match *left {
+                Token::Bitwise { left, .. } => assert_eq!("1 + 2", left.to_string()),
+                other => panic!("expected a bitwise operation, got {:?}", other),
+            },
+            other => panic!("expected a comparison, got {:?}", other),
+        }
+    }
+}
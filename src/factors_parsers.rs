@@ -1,170 +1,459 @@
 use nom::{
     branch::alt,
-    character::complete::{char, digit1, line_ending, multispace0},
-    combinator::{eof, map, map_res, opt},
-    error::context,
-    sequence::{delimited, preceded, terminated, tuple},
-    IResult,
+    bytes::complete::escaped_transform,
+    bytes::complete::{tag, tag_no_case, take_while, take_while1},
+    character::complete::{alpha1, alphanumeric1, char, digit1, none_of, one_of, satisfy},
+    combinator::{consumed, map, not, opt, peek, recognize, success, value, verify},
+    error::{context, ErrorKind, ParseError, VerboseError},
+    multi::many0_count,
+    sequence::{delimited, pair, preceded, terminated, tuple},
 };
 
-use crate::{expression_parsers::expression_parser, token::Token};
+use crate::{
+    block_parsers::if_parser,
+    expression_parsers::logical_parser,
+    function_parsers::call_parser,
+    operator_parsers::parenthesized,
+    span::{spanned, Input, ParseResult, Span},
+    token::Token,
+    trivia_parsers::{trailing_trivia, trivia},
+    type_checker::Type,
+};
 
-/// Parser for a `Factor`. A Factor consists of an integer, float, identifier,
-/// or a parenthized expression
+/// Parser for a `Factor`. A Factor consists of an integer, float, string,
+/// boolean, `if` expression, function call, identifier, negated factor, or a
+/// parenthized expression
 ///
 /// # Example
 ///
 /// ```
 /// use lrvmism::factors_parsers::factor_parser;
+/// use lrvmism::span::Span;
 /// use lrvmism::token::Token;
-/// fn test_integer(){
-///     let test = "(1+2)";
-///     let expressions = vec![Token::Expression {
-///        left: Box::new(Token::Term {
-///            left: Box::new(Token::Factor {
-///                value: Box::new(Token::Integer { value: 1 }),
-///            }),
-///            right: vec![],
-///        }),
-///        right: vec![(
-///            Token::AdditionOperator,
-///            Token::Term {
-///                left: Box::new(Token::Factor {
-///                    value: Box::new(Token::Integer { value: 2 }),
-///                }),
-///                right: vec![],
-///            },
-///        )],
-///     }];
-///     let result = factor_parser(test);
-///     assert!(result.is_ok());
-///     let (_reminder, program) = result.unwrap();
-///     assert_eq!(Token::Program { expressions }, program);
-///     assert!(_reminder.is_empty());
-/// }
+/// let (_reminder, factor) = factor_parser(" (1+2) ".into()).unwrap();
+/// assert!(matches!(factor, Token::Factor { .. }));
+/// assert_eq!("(1 + 2)", factor.to_string());
+/// let span = Span { offset: 1, length: 5, line: 1, column: 2 };
+/// assert_eq!(Some(span), factor.span());
+/// assert!(_reminder.is_empty());
 /// ```
 ///
-pub fn factor_parser(input: &str) -> IResult<&str, Token> {
+pub fn factor_parser(input: Input) -> ParseResult<Token> {
     context(
         "factor_parser",
         preceded(
-            multispace0,
+            trivia,
             terminated(
                 map(
                     alt((
-                        integer_parser,
-                        float64_parser,
-                        delimited(char('('), expression_parser, char(')')),
+                        map(
+                            alt((
+                                float64_parser,
+                                integer_parser,
+                                string_parser,
+                                boolean_parser,
+                                not_parser,
+                                if_parser,
+                                call_parser,
+                                identifier_parser,
+                            )),
+                            |f| {
+                                let span = f.span().expect("factors are nodes");
+                                (f, span)
+                            },
+                        ),
+                        spanned(delimited(
+                            char('('),
+                            parenthesized(logical_parser),
+                            preceded(trivia, char(')')),
+                        )),
                     )),
-                    |f| Token::Factor { value: Box::new(f) },
+                    |(f, span)| Token::Factor {
+                        value: Box::new(f),
+                        span,
+                    },
+                ),
+                trailing_trivia,
+            ),
+        ),
+    )(input)
+}
+
+/// Words that have a meaning of their own and so cannot be used as names
+pub const KEYWORDS: &[&str] = &[
+    "let", "if", "else", "while", "break", "continue", "fn", "return", "true", "false",
+];
+
+/// Recognizes the keyword `word`, as long as it is not just the start of a
+/// longer name (`if` matches in `if x` and `if(x)`, but not in `iffy`).
+pub fn keyword<'a>(word: &'static str) -> impl FnMut(Input<'a>) -> ParseResult<'a, Input<'a>> {
+    terminated(
+        tag(word),
+        not(peek(satisfy(|c: char| c.is_alphanumeric() || c == '_'))),
+    )
+}
+
+/// Recognizes a name: a letter or `_` followed by letters, digits and `_`s.
+/// Keywords are not names.
+///
+/// # Example
+///
+/// ```
+/// use lrvmism::factors_parsers::identifier;
+/// let (_reminder, name) = identifier("total_2 + 1".into()).unwrap();
+/// assert_eq!("total_2", *name.fragment());
+/// assert!(identifier("let".into()).is_err());
+/// ```
+pub fn identifier(input: Input) -> ParseResult<Input> {
+    context(
+        "identifier",
+        verify(
+            recognize(pair(
+                alt((alpha1, tag("_"))),
+                many0_count(alt((alphanumeric1, tag("_")))),
+            )),
+            |name: &Input| !KEYWORDS.contains(name.fragment()),
+        ),
+    )(input)
+}
+
+/// Parser for the name of a type in an annotation: `i32`, `f64`, `bool` or
+/// `str`.
+///
+/// # Example
+///
+/// ```
+/// use lrvmism::factors_parsers::type_parser;
+/// use lrvmism::type_checker::Type;
+/// let (_reminder, ty) = type_parser(" f64 = 1.5".into()).unwrap();
+/// assert_eq!(Type::Float, ty);
+/// assert!(type_parser("f64x".into()).is_err());
+/// ```
+pub fn type_parser(input: Input) -> ParseResult<Type> {
+    context(
+        "type_parser",
+        preceded(
+            trivia,
+            alt((
+                value(Type::Int, keyword("i32")),
+                value(Type::Float, keyword("f64")),
+                value(Type::Bool, keyword("bool")),
+                value(Type::String, keyword("str")),
+            )),
+        ),
+    )(input)
+}
+
+/// Parser for an optional type annotation, `: <type>`, after a name
+pub fn annotation_parser(input: Input) -> ParseResult<Option<Type>> {
+    context(
+        "annotation_parser",
+        opt(preceded(pair(trivia, char(':')), type_parser)),
+    )(input)
+}
+
+/// Parser for the literals `true` and `false`.
+///
+/// # Example
+///
+/// ```
+/// use lrvmism::factors_parsers::boolean_parser;
+/// use lrvmism::token::Token;
+/// let (_reminder, token) = boolean_parser(" true ".into()).unwrap();
+/// assert!(matches!(token, Token::Boolean { value: true, .. }));
+/// assert!(boolean_parser("falsey".into()).is_err());
+/// ```
+pub fn boolean_parser(input: Input) -> ParseResult<Token> {
+    context(
+        "boolean_parser",
+        preceded(
+            trivia,
+            terminated(
+                map(
+                    spanned(alt((
+                        value(true, keyword("true")),
+                        value(false, keyword("false")),
+                    ))),
+                    |(value, span)| Token::Boolean { value, span },
                 ),
-                alt((multispace0, eof, line_ending)),
+                trailing_trivia,
             ),
         ),
     )(input)
 }
 
-/// Parser for a signed 64-bit integer.
+/// Parser for `!` applied to a factor, e.g. `!done` or `!(a < b)`
+pub fn not_parser(input: Input) -> ParseResult<Token> {
+    context(
+        "not_parser",
+        preceded(
+            trivia,
+            map(
+                pair(
+                    spanned(terminated(char('!'), not(char('=')))),
+                    factor_parser,
+                ),
+                |((_, start), value)| Token::Not {
+                    span: start.to(value.span().expect("factors are nodes")),
+                    value: Box::new(value),
+                },
+            ),
+        ),
+    )(input)
+}
+
+/// Parser for an identifier used as a value, i.e. a reference to a binding.
+pub fn identifier_parser(input: Input) -> ParseResult<Token> {
+    context(
+        "identifier_parser",
+        preceded(
+            trivia,
+            terminated(
+                map(spanned(identifier), |(name, span)| Token::Identifier {
+                    name: name.to_string(),
+                    span,
+                }),
+                trailing_trivia,
+            ),
+        ),
+    )(input)
+}
+
+/// Parser for an integer literal: decimal, or hexadecimal, binary or
+/// octal behind a `0x`, `0b` or `0o` prefix, with any `_`s between the
+/// digits ignored, e.g. `1_000`, `0xFF` or `0b1010`. A literal has no sign:
+/// in `-4` the `-` is the negation operator, see `term_parsers::unary_parser`.
+///
+/// An int is 32 bits wide, like the lrvm registers that hold it. A decimal
+/// literal goes up to `i32::MAX`, and `2147483648` only directly after a `-`,
+/// see `minimum_magnitude_parser`. A prefixed literal is a bit pattern of up
+/// to 32 bits, so `0xFFFF_FFFF` is -1. A literal out of range fails with a
+/// `nom::Err::Failure` whose innermost error is of kind `ErrorKind::TooLarge`
+/// and has exactly the literal for its input, so that it can be reported
+/// with its span.
 ///
 /// # Example
 ///
 /// ```
 /// use lrvmism::factors_parsers::integer_parser;
 /// use lrvmism::token::Token;
-/// fn test_integer(){
-///     let test_arr = ["4"," -4 "];
-///     for input in test_arr {
-///         let expect = Token::Integer {
-///             value: input.parse::<i64>().unwrap(),
-///         };
-///         let result = integer_parser(input);
-///         assert!(result.is_ok());
-///         let (_reminder, value) = result.unwrap();
-///         assert_eq!(expect, value);
-///         assert!(_reminder.is_empty());
-///     }
+/// for (input, value) in [("4", 4), (" 1_000 ", 1000), ("0xff", 255), ("0b1010", 10), ("0o17", 15)] {
+///     let (_reminder, token) = integer_parser(input.into()).unwrap();
+///     assert!(matches!(token, Token::Integer { value: v, .. } if v == value));
+///     assert!(_reminder.is_empty());
 /// }
+/// assert!(integer_parser("0x".into()).is_err());
 /// ```
-pub fn integer_parser(input: &str) -> IResult<&str, Token> {
+pub fn integer_parser(input: Input) -> ParseResult<Token> {
+    context(
+        "integer_parser",
+        preceded(trivia, terminated(integer_literal, trailing_trivia)),
+    )(input)
+}
+
+fn integer_literal(input: Input) -> ParseResult<Token> {
+    let (rest, (literal, (radix, digits))) = consumed(terminated(
+        alt((
+            pair(value(16, tag_no_case("0x")), prefixed_digits(16)),
+            pair(value(2, tag_no_case("0b")), prefixed_digits(2)),
+            pair(value(8, tag_no_case("0o")), prefixed_digits(8)),
+            pair(success(10), decimal_digits),
+        )),
+        end_of_literal,
+    ))(input)?;
+    let digits: String = digits.chars().filter(|c| *c != '_').collect();
+    let value = if radix == 10 {
+        i32::from_str_radix(&digits, radix).ok()
+    } else {
+        u32::from_str_radix(&digits, radix)
+            .ok()
+            .map(|bits| bits as i32)
+    };
+    match value {
+        Some(value) => Ok((
+            rest,
+            Token::Integer {
+                value: value.into(),
+                span: Span::of(literal),
+            },
+        )),
+        None => Err(nom::Err::Failure(VerboseError::from_error_kind(
+            literal,
+            ErrorKind::TooLarge,
+        ))),
+    }
+}
+
+/// Parser for the factor `2147483648`, the magnitude of `i32::MIN`. It is
+/// one more than `i32::MAX`, so it is only a literal as the operand of a
+/// `-`, where `term_parsers::unary_parser` tries it first.
+///
+/// # Example
+///
+/// ```
+/// use lrvmism::factors_parsers::minimum_magnitude_parser;
+/// let (_reminder, factor) = minimum_magnitude_parser(" 2_147_483_648 ".into()).unwrap();
+/// assert_eq!("2147483648", factor.to_string());
+/// assert!(minimum_magnitude_parser("2147483647".into()).is_err());
+/// ```
+pub fn minimum_magnitude_parser(input: Input) -> ParseResult<Token> {
     context(
         "integer_parser",
         preceded(
-            multispace0,
+            trivia,
             terminated(
-                map_res(
-                    tuple((opt(char('-')), digit1)),
-                    |(sign, num): (Option<char>, &str)| {
-                        let value = match num.parse::<i64>() {
-                            Ok(v) => v,
-                            Err(e) => {
-                                eprintln!("Unable to parse the value: {}\nError: {}", num, e);
-                                return Err(num);
-                            }
-                        };
-
-                        let value = if sign.is_some() { -value } else { value };
-
-                        Ok::<Token, &str>(Token::Integer { value })
+                map(
+                    verify(
+                        recognize(terminated(decimal_digits, end_of_literal)),
+                        |literal: &Input| {
+                            let digits = literal.chars().filter(|c| *c != '_');
+                            digits.eq(MINIMUM_MAGNITUDE.to_string().chars())
+                        },
+                    ),
+                    |literal| {
+                        let span = Span::of(literal);
+                        Token::Factor {
+                            value: Box::new(Token::Integer {
+                                value: MINIMUM_MAGNITUDE,
+                                span,
+                            }),
+                            span,
+                        }
                     },
                 ),
-                alt((multispace0, eof, line_ending)),
+                trailing_trivia,
             ),
         ),
     )(input)
 }
 
-/// Parser for a 64-bit float. A float can be negative, and must contain a `.`.
+/// The magnitude of `i32::MIN`
+const MINIMUM_MAGNITUDE: i64 = 1 << 31;
+
+/// Parser for a 64-bit float literal: decimal digits with a fraction, an
+/// exponent or both, e.g. `1.5`, `.5`, `1e-9` or `6.022_e23`. Like an
+/// integer literal it has no sign, and `_`s between digits are ignored.
+///
+/// A literal too large for an `f64` fails like an integer literal that is
+/// too large, with `ErrorKind::Float` for its kind.
 ///
 /// # Example
 ///
 /// ```
-/// use lrvmism::factors_parsers::integer_parser;
+/// use lrvmism::factors_parsers::float64_parser;
 /// use lrvmism::token::Token;
-/// fn test_integer(){
-///     let test_arr = ["4.5"," -4.5 "];
-///     for input in test_arr {
-///         let expect = Token::Float {
-///             value: input.parse::<f64>().unwrap(),
-///         };
-///         let result = integer_parser(input);
-///         assert!(result.is_ok());
-///         let (_reminder, value) = result.unwrap();
-///         assert_eq!(expect, value);
-///         assert!(_reminder.is_empty());
-///     }
+/// for (input, value) in [("4.5", 4.5), (" .5 ", 0.5), ("1e-9", 1e-9), ("2.5E3", 2500.0)] {
+///     let (_reminder, token) = float64_parser(input.into()).unwrap();
+///     assert!(matches!(token, Token::Float { value: v, .. } if v == value));
+///     assert!(_reminder.is_empty());
 /// }
+/// assert!(float64_parser("4".into()).is_err());
 /// ```
-pub fn float64_parser(input: &str) -> IResult<&str, Token> {
+pub fn float64_parser(input: Input) -> ParseResult<Token> {
     context(
         "float64_parser",
+        preceded(trivia, terminated(float_literal, trailing_trivia)),
+    )(input)
+}
+
+fn float_literal(input: Input) -> ParseResult<Token> {
+    let (rest, literal) = terminated(
+        alt((
+            recognize(tuple((
+                decimal_digits,
+                char('.'),
+                decimal_digits,
+                opt(exponent),
+            ))),
+            recognize(tuple((char('.'), decimal_digits, opt(exponent)))),
+            recognize(pair(decimal_digits, exponent)),
+        )),
+        end_of_literal,
+    )(input)?;
+    let literal_text: String = literal.chars().filter(|c| *c != '_').collect();
+    match literal_text.parse::<f64>() {
+        Ok(value) if value.is_finite() => Ok((
+            rest,
+            Token::Float {
+                value,
+                span: Span::of(literal),
+            },
+        )),
+        _ => Err(nom::Err::Failure(VerboseError::from_error_kind(
+            literal,
+            ErrorKind::Float,
+        ))),
+    }
+}
+
+/// Recognizes decimal digits and the `_`s between them, starting with a
+/// digit
+fn decimal_digits(input: Input) -> ParseResult<Input> {
+    recognize(pair(
+        digit1,
+        take_while(|c: char| c.is_ascii_digit() || c == '_'),
+    ))(input)
+}
+
+/// Recognizes the digits of `radix` after a prefix like `0x`, where `_`s
+/// may come before the first digit as well
+fn prefixed_digits<'a>(radix: u32) -> impl FnMut(Input<'a>) -> ParseResult<'a, Input<'a>> {
+    recognize(tuple((
+        take_while(|c: char| c == '_'),
+        take_while1(move |c: char| c.is_digit(radix)),
+        take_while(move |c: char| c.is_digit(radix) || c == '_'),
+    )))
+}
+
+/// Recognizes the exponent of a float, e.g. `e-9`
+fn exponent(input: Input) -> ParseResult<Input> {
+    recognize(tuple((one_of("eE"), opt(one_of("+-")), decimal_digits)))(input)
+}
+
+/// Succeeds where a number ends: a letter, digit or `_` right after it would
+/// make it a malformed literal, like `12ab` or `0b102`
+fn end_of_literal(input: Input) -> ParseResult<()> {
+    not(peek(satisfy(|c: char| c.is_alphanumeric() || c == '_')))(input)
+}
+
+/// Parser for a string literal between double quotes. The escapes `\n`,
+/// `\t`, `\"` and `\\` stand for a newline, a tab, a quote and a backslash.
+///
+/// # Example
+///
+/// ```
+/// use lrvmism::factors_parsers::string_parser;
+/// use lrvmism::token::Token;
+/// let (_reminder, string) = string_parser(r#""say \"hi\"\n""#.into()).unwrap();
+/// assert!(matches!(string, Token::String { value, .. } if value == "say \"hi\"\n"));
+/// ```
+pub fn string_parser(input: Input) -> ParseResult<Token> {
+    context(
+        "string_parser",
         preceded(
-            multispace0,
+            trivia,
             terminated(
-                map_res(
-                    tuple((opt(char('-')), digit1, char('.'), digit1)),
-                    |(sign, left, _dot, right)| {
-                        let mut float_str = String::from(left);
-                        float_str.push_str(".");
-                        float_str.push_str(right);
-                        let converted_float = match float_str.parse::<f64>() {
-                            Ok(v) => v,
-                            Err(e) => {
-                                eprintln!("Unable to parse the value: {}\nError: {}", float_str, e);
-                                return Err(float_str);
-                            }
-                        };
-
-                        let value = if sign.is_some() {
-                            -converted_float
-                        } else {
-                            converted_float
-                        };
-                        Ok(Token::Factor {
-                            value: Box::new(Token::Float { value }),
-                        })
+                map(
+                    spanned(delimited(
+                        char('"'),
+                        opt(escaped_transform(
+                            none_of("\\\""),
+                            '\\',
+                            alt((
+                                value("\n", char('n')),
+                                value("\t", char('t')),
+                                value("\"", char('"')),
+                                value("\\", char('\\')),
+                            )),
+                        )),
+                        char('"'),
+                    )),
+                    |(value, span)| Token::String {
+                        value: value.unwrap_or_default(),
+                        span,
                     },
                 ),
-                alt((multispace0, line_ending, eof)),
+                trailing_trivia,
             ),
         ),
     )(input)
@@ -172,77 +461,218 @@ pub fn float64_parser(input: &str) -> IResult<&str, Token> {
 
 #[cfg(test)]
 mod tests {
-    use crate::{factors_parsers::factor_parser, token::Token};
+    use crate::{factors_parsers::factor_parser, span::Span, token::Token};
 
-    use super::{float64_parser, integer_parser};
+    use nom::error::{ErrorKind, VerboseErrorKind};
+
+    use super::{float64_parser, identifier_parser, integer_parser, not_parser, string_parser};
+
+    /// The span of `text` in the one-line `input`
+    fn span_of(input: &str, text: &str) -> Span {
+        let offset = input.find(text).unwrap();
+        Span {
+            offset,
+            length: text.len(),
+            line: 1,
+            column: offset + 1,
+        }
+    }
 
     #[test]
     fn test_parse_integer() {
-        let test_array = vec!["0", "-1", "1", "-64", "64"];
+        let test_array = vec!["0", "1", "64"];
         for input in test_array {
             let expect = Token::Integer {
                 value: input.parse::<i64>().unwrap(),
+                span: span_of(input, input),
             };
 
-            let result = integer_parser(input);
+            let result = integer_parser(input.into());
             assert!(result.is_ok());
 
             let (_reminder, value) = result.unwrap();
             assert_eq!(expect, value);
             assert!(_reminder.is_empty());
         }
+        // The sign is the negation operator, not part of the literal
+        assert!(integer_parser("-1".into()).is_err());
     }
 
     #[test]
     fn test_float64_parser() {
         let input = "1.2";
-        let result = float64_parser(input);
+        let result = float64_parser(input.into());
         assert_eq!(result.is_ok(), true);
 
-        let input_arr = ["1.0", " -1.0 ", "323.8", "1.453"];
-        let expect_arr = [1.0, -1.0, 323.8, 1.453];
+        let input_arr = ["1.0", " 1.0 ", "323.8", "1.453"];
+        let expect_arr = [1.0, 1.0, 323.8, 1.453];
         for (i, test_str) in input_arr.iter().enumerate() {
-            let result = float64_parser(test_str);
+            let result = float64_parser((*test_str).into());
             assert_eq!(result.is_ok(), true);
 
             let (_reminder, value) = result.unwrap();
-            let expect = Token::Factor {
-                value: Box::new(Token::Float {
-                    value: expect_arr[i],
-                }),
+            let expect = Token::Float {
+                value: expect_arr[i],
+                span: span_of(test_str, test_str.trim()),
             };
 
             assert!(_reminder.is_empty());
             assert_eq!(expect, value);
         }
+        assert!(float64_parser("-1.0".into()).is_err());
     }
 
     #[test]
     fn test_factor() {
         let test_program = ("(1+2)");
-        let result = factor_parser(test_program);
+        let result = factor_parser(test_program.into());
         assert_eq!(result.is_ok(), true);
         let (_, tree) = result.unwrap();
         println!("{:#?}", tree);
     }
 
+    #[test]
+    fn test_identifier_parser() {
+        for input in ["x", " total ", "_tmp", "a1_b2", "lettuce"] {
+            let result = identifier_parser(input.into());
+            assert!(result.is_ok(), "{}", input);
+            let (_reminder, token) = result.unwrap();
+            assert_eq!(
+                Token::Identifier {
+                    name: input.trim().to_string(),
+                    span: span_of(input, input.trim()),
+                },
+                token
+            );
+            assert!(_reminder.is_empty());
+        }
+        for input in ["1x", "let", "true", ""] {
+            assert!(identifier_parser(input.into()).is_err(), "{}", input);
+        }
+    }
+
     #[test]
     fn test_parse_floats() {
-        let test_floats = vec!["100.4", "1.02", "-1.02"];
+        let test_floats = vec!["100.4", "1.02"];
         for o in test_floats {
             let _parsed_o = o.parse::<f64>().unwrap();
-            let result = float64_parser(o);
+            let result = float64_parser(o.into());
             assert_eq!(result.is_ok(), true);
         }
     }
 
     #[test]
     fn test_parse_integer_2() {
-        let test_integers = vec!["0", "-1", "1"];
+        let test_integers = vec!["0", "1"];
         for o in test_integers {
             let _parsed_o = o.parse::<i64>().unwrap();
-            let result = integer_parser(o);
+            let result = integer_parser(o.into());
             assert_eq!(result.is_ok(), true);
         }
     }
+
+    #[test]
+    fn test_parse_string() {
+        for (input, expected, rest) in [
+            (r#""hello""#, "hello", ""),
+            (r#""""#, "", ""),
+            (r#" "tab\tnew\nline" + 1"#, "tab\tnew\nline", "+ 1"),
+            (r#""back\\slash \"quoted\"""#, "back\\slash \"quoted\"", ""),
+        ] {
+            let (r, token) = string_parser(input.into()).unwrap();
+            let literal = input.trim_start().strip_suffix(rest).unwrap().trim_end();
+            assert_eq!(
+                Token::String {
+                    value: expected.to_string(),
+                    span: span_of(input, literal),
+                },
+                token
+            );
+            assert_eq!(rest, *r.fragment());
+        }
+        assert!(string_parser(r#""unterminated"#.into()).is_err());
+        assert!(string_parser(r#""bad \q escape""#.into()).is_err());
+    }
+
+    #[test]
+    fn test_not_parser() {
+        let (r, token) = not_parser("!done && x".into()).unwrap();
+        assert_eq!("&& x", *r.fragment());
+        assert_eq!("!done", token.to_string());
+        assert_eq!(Some(span_of("!done && x", "!done")), token.span());
+        let (_, token) = not_parser(" !!(a < b)".into()).unwrap();
+        assert_eq!("!!(a < b)", token.to_string());
+        assert!(not_parser("!= 1".into()).is_err());
+    }
+
+    #[test]
+    fn test_numeric_literals() {
+        for (input, value) in [
+            ("0xFF", 255),
+            ("0Xff", 255),
+            ("0x_ff", 255),
+            ("0b1010", 10),
+            ("0b_1111_0000", 240),
+            ("0o777", 511),
+            ("1_000_000", 1_000_000),
+            ("2147483647", i32::MAX.into()),
+            ("0x7FFF_FFFF", i32::MAX.into()),
+            ("0x8000_0000", i32::MIN.into()),
+            ("0xFFFF_FFFF", -1),
+            ("0b1000_0000_0000_0000_0000_0000_0000_0001", -2147483647),
+        ] {
+            let (r, token) = integer_parser(input.into()).unwrap();
+            assert!(r.is_empty(), "{}", input);
+            let span = span_of(input, input);
+            assert_eq!(Token::Integer { value, span }, token, "{}", input);
+        }
+        for (input, value) in [
+            (".5", 0.5),
+            ("1e-9", 1e-9),
+            ("1E3", 1000.0),
+            ("1_000.25", 1000.25),
+            ("6.022e23", 6.022e23),
+            ("2.5e+2", 250.0),
+        ] {
+            let (r, token) = float64_parser(input.into()).unwrap();
+            assert!(r.is_empty(), "{}", input);
+            let span = span_of(input, input);
+            assert_eq!(Token::Float { value, span }, token, "{}", input);
+        }
+        for input in ["0x", "0b102", "12ab", "_1", "0o8"] {
+            assert!(integer_parser(input.into()).is_err(), "{}", input);
+        }
+        for input in ["1.", "1e", "1.5x", "."] {
+            assert!(float64_parser(input.into()).is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_out_of_range_literals() {
+        for (input, literal, code) in [
+            ("2147483648", "2147483648", ErrorKind::TooLarge),
+            ("0x1_0000_0000", "0x1_0000_0000", ErrorKind::TooLarge),
+            (
+                "9223372036854775808",
+                "9223372036854775808",
+                ErrorKind::TooLarge,
+            ),
+            (
+                " 0x1_0000_0000_0000_0000 + 1",
+                "0x1_0000_0000_0000_0000",
+                ErrorKind::TooLarge,
+            ),
+            ("1e400", "1e400", ErrorKind::Float),
+        ] {
+            match factor_parser(input.into()) {
+                Err(nom::Err::Failure(e)) => {
+                    let (found, kind) = &e.errors[0];
+                    assert_eq!(literal, *found.fragment(), "{}", input);
+                    assert_eq!(span_of(input, literal), Span::of(*found), "{}", input);
+                    assert_eq!(&VerboseErrorKind::Nom(code), kind, "{}", input);
+                }
+                other => panic!("expected a failure for {}, got {:?}", input, other),
+            }
+        }
+    }
 }
@@ -0,0 +1,296 @@
+use nom::{
+    bytes::complete::tag,
+    character::complete::char,
+    combinator::{cut, map, opt},
+    error::context,
+    multi::separated_list0,
+    sequence::{delimited, pair, preceded, terminated, tuple},
+};
+
+use crate::{
+    block_parsers::block_parser,
+    expression_parsers::logical_parser,
+    factors_parsers::{annotation_parser, identifier, keyword, type_parser},
+    operator_parsers::parenthesized,
+    span::{spanned, Input, ParseResult},
+    token::Token,
+    trivia_parsers::{same_line, trailing_trivia, trivia},
+};
+
+/// Parser for a function definition, `fn <name>(<parameters>) { ... }`. The
+/// function gives back the value of a `return`, or else of its block.
+/// Parameters can be annotated with their types and the function with the
+/// type it returns, as in `fn half(x: f64) -> f64 { x / 2.0 }`.
+///
+/// # Example
+///
+/// ```
+/// use lrvmism::function_parsers::function_parser;
+/// let result = function_parser("fn add(a, b) {\n  return a + b\n}".into());
+/// assert!(result.is_ok());
+/// assert!(result.unwrap().0.is_empty());
+/// ```
+pub fn function_parser(input: Input) -> ParseResult<Token> {
+    context(
+        "function_parser",
+        preceded(
+            trivia,
+            map(
+                pair(
+                    spanned(keyword("fn")),
+                    cut(tuple((
+                        trivia,
+                        identifier,
+                        trivia,
+                        delimited(
+                            char('('),
+                            separated_list0(
+                                char(','),
+                                delimited(trivia, pair(identifier, annotation_parser), trivia),
+                            ),
+                            char(')'),
+                        ),
+                        opt(preceded(pair(trivia, tag("->")), type_parser)),
+                        block_parser,
+                        trailing_trivia,
+                    ))),
+                ),
+                |((_, start), (_, name, _, parameters, return_type, body, _))| Token::Function {
+                    span: start.to(body.span().expect("blocks are nodes")),
+                    name: name.to_string(),
+                    parameters: parameters
+                        .into_iter()
+                        .map(|(parameter, annotation)| (parameter.to_string(), annotation))
+                        .collect(),
+                    return_type,
+                    body: Box::new(body),
+                },
+            ),
+        ),
+    )(input)
+}
+
+/// Parser for `return` with an optional value, which leaves the function it
+/// is in. The value has to start on the same line as the `return`, as a
+/// newline ends the statement.
+pub fn return_parser(input: Input) -> ParseResult<Token> {
+    context(
+        "return_parser",
+        preceded(
+            trivia,
+            map(
+                pair(
+                    spanned(keyword("return")),
+                    terminated(opt(preceded(same_line, logical_parser)), trailing_trivia),
+                ),
+                |((_, start), value)| Token::Return {
+                    span: match &value {
+                        Some(value) => start.to(value.span().expect("values are nodes")),
+                        None => start,
+                    },
+                    value: value.map(Box::new),
+                },
+            ),
+        ),
+    )(input)
+}
+
+/// Parser for a call, `<name>(<arguments>)`. The `(` has to follow the name
+/// directly, so a name and a parenthized expression are never mistaken for
+/// a call.
+///
+/// # Example
+///
+/// ```
+/// use lrvmism::function_parsers::call_parser;
+/// use lrvmism::token::Token;
+/// let (_reminder, call) = call_parser("max(1, 2)".into()).unwrap();
+/// assert!(matches!(call, Token::Call { ref arguments, .. } if arguments.len() == 2));
+/// assert!(call_parser("max (1, 2)".into()).is_err());
+/// ```
+pub fn call_parser(input: Input) -> ParseResult<Token> {
+    context(
+        "call_parser",
+        preceded(
+            trivia,
+            terminated(
+                map(
+                    spanned(pair(
+                        identifier,
+                        delimited(
+                            char('('),
+                            parenthesized(separated_list0(
+                                preceded(trivia, char(',')),
+                                logical_parser,
+                            )),
+                            preceded(trivia, char(')')),
+                        ),
+                    )),
+                    |((name, arguments), span)| Token::Call {
+                        name: name.to_string(),
+                        arguments,
+                        span,
+                    },
+                ),
+                trailing_trivia,
+            ),
+        ),
+    )(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{span::Span, token::Token, type_checker::Type};
+
+    use super::{call_parser, function_parser, return_parser};
+
+    #[test]
+    fn test_function_parser() {
+        let (r, token) = function_parser(
+            "fn fib(n) {\n  if n < 2 { return n }\n  fib(n - 1) + fib(n - 2)\n}\n".into(),
+        )
+        .unwrap();
+        assert_eq!("\n", *r.fragment());
+        match token {
+            Token::Function {
+                name,
+                parameters,
+                return_type,
+                body,
+                ..
+            } => {
+                assert_eq!("fib", name);
+                assert_eq!(vec![("n".to_string(), None)], parameters);
+                assert_eq!(None, return_type);
+                assert!(
+                    matches!(*body, Token::Block { ref statements, .. } if statements.len() == 2)
+                );
+            }
+            other => panic!("expected a function, got {:?}", other),
+        }
+
+        let (_, token) = function_parser("fn zero() { 0 }".into()).unwrap();
+        assert!(matches!(token, Token::Function { ref parameters, .. } if parameters.is_empty()));
+        assert!(function_parser("fn 1() { 0 }".into()).is_err());
+        assert!(function_parser("fnord() { 0 }".into()).is_err());
+    }
+
+    #[test]
+    fn test_function_annotations() {
+        let (r, token) = function_parser("fn mix(a: f64, b, c :i32)->f64 { a }".into()).unwrap();
+        assert!(r.is_empty());
+        let a = Span {
+            offset: 33,
+            length: 1,
+            line: 1,
+            column: 34,
+        };
+        assert_eq!(
+            Token::Function {
+                name: "mix".to_string(),
+                parameters: vec![
+                    ("a".to_string(), Some(Type::Float)),
+                    ("b".to_string(), None),
+                    ("c".to_string(), Some(Type::Int)),
+                ],
+                return_type: Some(Type::Float),
+                body: Box::new(Token::Block {
+                    statements: vec![Token::Expression {
+                        left: Box::new(Token::Term {
+                            left: Box::new(Token::Factor {
+                                value: Box::new(Token::Identifier {
+                                    name: "a".to_string(),
+                                    span: a,
+                                }),
+                                span: a,
+                            }),
+                            right: vec![],
+                            span: a,
+                        }),
+                        right: vec![],
+                        span: a,
+                    }],
+                    span: Span {
+                        offset: 31,
+                        length: 5,
+                        line: 1,
+                        column: 32,
+                    },
+                }),
+                span: Span {
+                    offset: 0,
+                    length: 36,
+                    line: 1,
+                    column: 1,
+                },
+            },
+            token
+        );
+        assert!(function_parser("fn f(a: x) { a }".into()).is_err());
+        assert!(function_parser("fn f() -> { 1 }".into()).is_err());
+    }
+
+    #[test]
+    fn test_return_parser() {
+        let (r, token) = return_parser("return a + b\n".into()).unwrap();
+        assert_eq!("\n", *r.fragment());
+        assert_eq!("return a + b", token.to_string());
+
+        let (r, token) = return_parser("return\n  x".into()).unwrap();
+        assert_eq!("\n  x", *r.fragment());
+        assert!(matches!(token, Token::Return { value: None, .. }));
+
+        let (r, token) = return_parser("return }".into()).unwrap();
+        assert_eq!("}", *r.fragment());
+        assert_eq!(
+            Token::Return {
+                value: None,
+                span: Span {
+                    offset: 0,
+                    length: 6,
+                    line: 1,
+                    column: 1,
+                },
+            },
+            token
+        );
+
+        let (_, token) = return_parser(
+            "  return x
+"
+            .into(),
+        )
+        .unwrap();
+        assert_eq!(
+            Some(Span {
+                offset: 2,
+                length: 8,
+                line: 1,
+                column: 3,
+            }),
+            token.span()
+        );
+    }
+
+    #[test]
+    fn test_call_parser() {
+        let (r, token) = call_parser("add(1, x * 2) + 3".into()).unwrap();
+        assert_eq!("+ 3", *r.fragment());
+        assert_eq!("add(1, x * 2)", token.to_string());
+
+        let (_, token) = call_parser("now()".into()).unwrap();
+        assert_eq!(
+            Token::Call {
+                name: "now".to_string(),
+                arguments: vec![],
+                span: Span {
+                    offset: 0,
+                    length: 5,
+                    line: 1,
+                    column: 1,
+                },
+            },
+            token
+        );
+    }
+}
@@ -1,9 +1,19 @@
+pub mod block_parsers;
+pub mod driver;
+pub mod error;
 pub mod expression_parsers;
 pub mod factors_parsers;
+pub mod function_parsers;
 pub mod operator_parsers;
 pub mod program_parsers;
+pub mod repl;
+pub mod runtime;
+pub mod span;
+pub mod statement_parsers;
 pub mod term_parsers;
 pub mod token;
+pub mod trivia_parsers;
+pub mod type_checker;
 pub mod vistor;
 
 extern crate lrvm;
@@ -1,3 +1,195 @@
-fn main() {
-    println!("Hello, world!");
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use lrvmism::{
+    driver::{self, ExitState},
+    error::CompileError,
+    repl::REPL,
+};
+
+/// Compiles an lrvmism source file into lrvm bytecode
+#[derive(Parser, Debug)]
+#[command(
+    name = "lrvmism",
+    version,
+    about,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[command(flatten)]
+    build: BuildArgs,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Compiles a source file and runs it on the lrvm VM
+    Run {
+        /// Source file to run
+        input: PathBuf,
+    },
+    /// Starts an interactive prompt backed by a persistent VM
+    Repl,
+}
+
+/// Arguments for the default mode, which compiles a file to bytecode
+#[derive(Args, Debug)]
+struct BuildArgs {
+    /// Source file to compile
+    #[arg(required = true)]
+    input: Option<PathBuf>,
+    /// Where to write the output. Defaults to the input path with a `.bin`
+    /// extension, or to stdout when `--emit` is given
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    /// Stop the pipeline after the given stage and dump its result instead of
+    /// writing bytecode
+    #[arg(long, value_enum)]
+    emit: Option<Emit>,
+}
+
+/// The pipeline stages that can be dumped with `--emit`
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Emit {
+    /// The lexemes of the parsed program, one per line
+    Tokens,
+    /// The `Token` tree produced by `program_parser`
+    Ast,
+    /// The assembly generated by the `Compiler`
+    Asm,
+    /// A hex dump of the assembled bytecode
+    Bytecode,
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match cli.command {
+        Some(Command::Run { input }) => run(&input),
+        Some(Command::Repl) => {
+            REPL::new().run();
+            ExitCode::SUCCESS
+        }
+        None => build(cli.build),
+    }
+}
+
+/// Reads the source file at `path`, reporting any failure on stderr
+fn read_source(path: &Path) -> Option<String> {
+    match fs::read_to_string(path) {
+        Ok(s) => Some(s),
+        Err(e) => {
+            eprintln!("error: unable to read {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+fn run(input: &Path) -> ExitCode {
+    let Some(source) = read_source(input) else {
+        return ExitCode::FAILURE;
+    };
+    let result = match driver::run(&source) {
+        Ok(r) => r,
+        Err(e) => {
+            report(input, &e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    if let Some(value) = result.value {
+        println!("result: {}", value);
+    }
+    match result.exit {
+        ExitState::Halted { code } => {
+            println!("exit: halted (code {})", code);
+            ExitCode::SUCCESS
+        }
+        ExitState::Crashed { code } => {
+            eprintln!("exit: crashed (code {})", code);
+            ExitCode::FAILURE
+        }
+        ExitState::Unknown => {
+            eprintln!("exit: unknown");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn build(args: BuildArgs) -> ExitCode {
+    let input = args.input.expect("clap requires an input file");
+    let Some(source) = read_source(&input) else {
+        return ExitCode::FAILURE;
+    };
+
+    let result = match args.emit {
+        Some(stage) => emit(&source, stage).map(String::into_bytes),
+        None => driver::compile(&source),
+    };
+    let output = match result {
+        Ok(o) => o,
+        Err(e) => {
+            report(&input, &e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let destination = match (args.output, args.emit) {
+        (Some(path), _) => Some(path),
+        (None, Some(_)) => None,
+        (None, None) => Some(input.with_extension("bin")),
+    };
+    if let Err(e) = write_output(destination.as_deref(), &output) {
+        eprintln!("error: unable to write output: {}", e);
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
+
+/// Reports `error`, found in the source file at `input`, on stderr. Syntax
+/// errors show the offending line of the source, and every error found while
+/// parsing is reported.
+fn report(input: &Path, error: &CompileError) {
+    match error {
+        CompileError::Syntax(error) => eprintln!("{}: {}", input.display(), error),
+        CompileError::Diagnostics(diagnostics) => {
+            for error in &diagnostics.errors {
+                report(input, error);
+            }
+        }
+        error => eprintln!("error: {}: {}", input.display(), error),
+    }
+}
+
+/// Runs the pipeline up to `stage` and renders that stage as text
+fn emit(source: &str, stage: Emit) -> Result<String, CompileError> {
+    let text = match stage {
+        Emit::Tokens => {
+            let mut lexemes = driver::parse(source)?.lexemes().join("\n");
+            lexemes.push('\n');
+            lexemes
+        }
+        Emit::Ast => format!("{:#?}\n", driver::parse(source)?),
+        Emit::Asm => {
+            let mut assembly = driver::codegen(source)?.assembly();
+            assembly.push('\n');
+            assembly
+        }
+        Emit::Bytecode => driver::hex_dump(&driver::compile(source)?),
+    };
+    Ok(text)
+}
+
+/// Writes `bytes` to `path`, or to stdout when there is no path
+fn write_output(path: Option<&Path>, bytes: &[u8]) -> io::Result<()> {
+    match path {
+        Some(path) => fs::write(path, bytes),
+        None => io::stdout().write_all(bytes),
+    }
 }
@@ -1,15 +1,79 @@
 use nom::{
     branch::alt,
-    character::complete::{char, line_ending, multispace0},
-    combinator::{eof, map},
+    bytes::complete::tag,
+    character::complete::char,
+    combinator::{map, not},
     error::context,
     sequence::{preceded, terminated},
-    IResult,
 };
 
-use crate::token::Token;
+use crate::{
+    span::{Input, ParseResult},
+    token::Token,
+    trivia_parsers::{same_line, trivia},
+};
+
+/// Runs the infix operator `parser`, which has to be on the same line as its
+/// left operand. This is how a newline ends a statement: a line that starts
+/// with `-` or `+` is a new statement with a sign, not the rest of the one
+/// before, so `let x = 5\n-x` is two statements. An operator at the end of a
+/// line does go on with the next one, as in `1 +\n2`.
+///
+/// Inside parentheses no statement can end, so there the operator may start
+/// a line, as in `(1\n- 2)`. The input says whether it is inside them, see
+/// `parenthesized`.
+///
+/// # Example
+///
+/// ```
+/// use lrvmism::operator_parsers::{infix, parenthesized, substraction_operator};
+/// use nom::{character::complete::char, sequence::pair};
+/// let mut difference = pair(char('x'), infix(substraction_operator));
+/// assert!(difference("x - 1".into()).is_ok());
+/// assert!(difference("x\n-1".into()).is_err());
+/// assert!(parenthesized(difference)("x\n-1".into()).is_ok());
+/// ```
+pub fn infix<'a>(
+    mut parser: impl FnMut(Input<'a>) -> ParseResult<'a, Token>,
+) -> impl FnMut(Input<'a>) -> ParseResult<'a, Token> {
+    move |input: Input<'a>| {
+        let input = if input.extra {
+            trivia(input)?.0
+        } else {
+            same_line(input)?.0
+        };
+        parser(input)
+    }
+}
+
+/// Runs `parser` on what is between parentheses, marking its input as inside
+/// them so that an infix operator may start a line
+pub fn parenthesized<'a, O>(
+    parser: impl FnMut(Input<'a>) -> ParseResult<'a, O>,
+) -> impl FnMut(Input<'a>) -> ParseResult<'a, O> {
+    with_parenthesized(true, parser)
+}
+
+/// Runs `parser` on statements, which a newline ends again even when they
+/// are in a block inside parentheses, so it marks its input as outside
+pub fn unparenthesized<'a, O>(
+    parser: impl FnMut(Input<'a>) -> ParseResult<'a, O>,
+) -> impl FnMut(Input<'a>) -> ParseResult<'a, O> {
+    with_parenthesized(false, parser)
+}
 
-pub fn operator(input: &str) -> IResult<&str, Token> {
+fn with_parenthesized<'a, O>(
+    parenthesized: bool,
+    mut parser: impl FnMut(Input<'a>) -> ParseResult<'a, O>,
+) -> impl FnMut(Input<'a>) -> ParseResult<'a, O> {
+    move |input: Input<'a>| {
+        let outer = input.extra;
+        let (rest, output) = parser(input.map_extra(|_| parenthesized))?;
+        Ok((rest.map_extra(|_| outer), output))
+    }
+}
+
+pub fn operator(input: Input) -> ParseResult<Token> {
     context(
         "operator",
         alt((
@@ -17,62 +81,217 @@ pub fn operator(input: &str) -> IResult<&str, Token> {
             substraction_operator,
             multiplication_operator,
             division_operator,
+            modulo_operator,
+            power_operator,
+            bit_and_operator,
+            bit_or_operator,
+            bit_xor_operator,
+            shift_left_operator,
+            shift_right_operator,
         )),
     )(input)
 }
 
-pub fn addition_operator(input: &str) -> IResult<&str, Token> {
+pub fn addition_operator(input: Input) -> ParseResult<Token> {
     context(
         "addition_operator",
         preceded(
-            multispace0,
+            trivia,
+            terminated(map(char('+'), |_| Token::AdditionOperator), trivia),
+        ),
+    )(input)
+}
+
+pub fn substraction_operator(input: Input) -> ParseResult<Token> {
+    context(
+        "substraction_operator",
+        preceded(
+            trivia,
+            terminated(map(char('-'), |_| Token::SubtractionOperator), trivia),
+        ),
+    )(input)
+}
+
+pub fn multiplication_operator(input: Input) -> ParseResult<Token> {
+    context(
+        "multiplication_operator",
+        preceded(
+            trivia,
             terminated(
-                map(char('+'), |_| Token::AdditionOperator),
-                alt((multispace0, eof, line_ending)),
+                // `**` is the exponent
+                map(terminated(char('*'), not(char('*'))), |_| {
+                    Token::MultiplicationOperator
+                }),
+                trivia,
             ),
         ),
     )(input)
 }
 
-pub fn substraction_operator(input: &str) -> IResult<&str, Token> {
+pub fn division_operator(input: Input) -> ParseResult<Token> {
     context(
-        "substraction_operator",
+        "division_operator",
+        preceded(
+            trivia,
+            terminated(map(char('/'), |_| Token::DivisionOperator), trivia),
+        ),
+    )(input)
+}
+
+pub fn modulo_operator(input: Input) -> ParseResult<Token> {
+    context(
+        "modulo_operator",
         preceded(
-            multispace0,
+            trivia,
+            terminated(map(char('%'), |_| Token::ModuloOperator), trivia),
+        ),
+    )(input)
+}
+
+pub fn power_operator(input: Input) -> ParseResult<Token> {
+    context(
+        "power_operator",
+        preceded(
+            trivia,
+            terminated(map(tag("**"), |_| Token::PowerOperator), trivia),
+        ),
+    )(input)
+}
+
+/// Parser for the bitwise and, `&`, which is not the start of `&&`
+pub fn bit_and_operator(input: Input) -> ParseResult<Token> {
+    context(
+        "bit_and_operator",
+        preceded(
+            trivia,
             terminated(
-                map(char('-'), |_| Token::SubtractionOperator),
-                alt((multispace0, eof, line_ending)),
+                map(terminated(char('&'), not(char('&'))), |_| {
+                    Token::BitAndOperator
+                }),
+                trivia,
             ),
         ),
     )(input)
 }
 
-pub fn multiplication_operator(input: &str) -> IResult<&str, Token> {
+/// Parser for the bitwise or, `|`, which is not the start of `||`
+pub fn bit_or_operator(input: Input) -> ParseResult<Token> {
     context(
-        "multiplication_operator",
+        "bit_or_operator",
         preceded(
-            multispace0,
+            trivia,
             terminated(
-                map(char('*'), |_| Token::MultiplicationOperator),
-                alt((multispace0, eof, line_ending)),
+                map(terminated(char('|'), not(char('|'))), |_| {
+                    Token::BitOrOperator
+                }),
+                trivia,
             ),
         ),
     )(input)
 }
 
-pub fn division_operator(input: &str) -> IResult<&str, Token> {
+pub fn bit_xor_operator(input: Input) -> ParseResult<Token> {
     context(
-        "division_operator",
+        "bit_xor_operator",
+        preceded(
+            trivia,
+            terminated(map(char('^'), |_| Token::BitXorOperator), trivia),
+        ),
+    )(input)
+}
+
+pub fn shift_left_operator(input: Input) -> ParseResult<Token> {
+    context(
+        "shift_left_operator",
+        preceded(
+            trivia,
+            terminated(map(tag("<<"), |_| Token::ShiftLeftOperator), trivia),
+        ),
+    )(input)
+}
+
+pub fn shift_right_operator(input: Input) -> ParseResult<Token> {
+    context(
+        "shift_right_operator",
+        preceded(
+            trivia,
+            terminated(map(tag(">>"), |_| Token::ShiftRightOperator), trivia),
+        ),
+    )(input)
+}
+
+/// Parser for `=` and the compound assignment operators `+=`, `-=`, `*=`
+/// and `/=`.
+pub fn assignment_operator(input: Input) -> ParseResult<Token> {
+    context(
+        "assignment_operator",
+        preceded(
+            trivia,
+            terminated(
+                alt((
+                    map(tag("+="), |_| Token::AddAssignOperator),
+                    map(tag("-="), |_| Token::SubtractAssignOperator),
+                    map(tag("*="), |_| Token::MultiplyAssignOperator),
+                    map(tag("/="), |_| Token::DivideAssignOperator),
+                    // `==` is a comparison, not an assignment
+                    map(terminated(char('='), not(char('='))), |_| {
+                        Token::AssignOperator
+                    }),
+                )),
+                trivia,
+            ),
+        ),
+    )(input)
+}
+
+/// Parser for the comparison operators `==`, `!=`, `>=`, `<=`, `>` and `<`.
+pub fn comparison_operator(input: Input) -> ParseResult<Token> {
+    context(
+        "comparison_operator",
         preceded(
-            multispace0,
+            trivia,
             terminated(
-                map(char('/'), |_| Token::DivisionOperator),
-                alt((multispace0, eof, line_ending)),
+                alt((
+                    map(tag("=="), |_| Token::EqualOperator),
+                    map(tag("!="), |_| Token::NotEqualOperator),
+                    map(tag(">="), |_| Token::GreaterThanOrEqualOperator),
+                    map(tag("<="), |_| Token::LessThanOrEqualOperator),
+                    // `>>` and `<<` are shifts
+                    map(terminated(char('>'), not(char('>'))), |_| {
+                        Token::GreaterThanOperator
+                    }),
+                    map(terminated(char('<'), not(char('<'))), |_| {
+                        Token::LessThanOperator
+                    }),
+                )),
+                trivia,
             ),
         ),
     )(input)
 }
 
+/// Parser for the logical and, `&&`
+pub fn and_operator(input: Input) -> ParseResult<Token> {
+    context(
+        "and_operator",
+        preceded(
+            trivia,
+            terminated(map(tag("&&"), |_| Token::AndOperator), trivia),
+        ),
+    )(input)
+}
+
+/// Parser for the logical or, `||`
+pub fn or_operator(input: Input) -> ParseResult<Token> {
+    context(
+        "or_operator",
+        preceded(
+            trivia,
+            terminated(map(tag("||"), |_| Token::OrOperator), trivia),
+        ),
+    )(input)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -82,7 +301,7 @@ mod tests {
         let test_arr = vec!["  +   ", "  - ", " * ", " / "];
         // let expect: Vec<usize> = vec![0, 1, 2, 3]; // +, -, *, /
         for (i, input) in test_arr.iter().enumerate() {
-            let result = operator(input);
+            let result = operator((*input).into());
             assert_eq!(result.is_ok(), true);
 
             let (r, t) = result.unwrap();
@@ -90,4 +309,80 @@ mod tests {
             assert_eq!(i, t.into());
         }
     }
+
+    #[test]
+    fn test_assignment_operator() {
+        let test_arr = [" = ", "+=", " -= ", "*=", "/= "];
+        for (i, input) in test_arr.iter().enumerate() {
+            let result = assignment_operator((*input).into());
+            assert!(result.is_ok(), "{}", input);
+
+            let (r, t) = result.unwrap();
+            assert!(r.is_empty());
+            assert_eq!(12 + i, t.into());
+        }
+        assert!(assignment_operator("==".into()).is_err());
+    }
+
+    #[test]
+    fn test_comparison_operator() {
+        let test_arr = ["==", " != ", ">", "<", " >= ", "<="];
+        for (i, input) in test_arr.iter().enumerate() {
+            let result = comparison_operator((*input).into());
+            assert!(result.is_ok(), "{}", input);
+
+            let (r, t) = result.unwrap();
+            assert!(r.is_empty());
+            assert_eq!(18 + i, t.into());
+        }
+        assert!(comparison_operator("=".into()).is_err());
+        assert!(comparison_operator("<<".into()).is_err());
+        assert!(comparison_operator(">> 1".into()).is_err());
+    }
+
+    #[test]
+    fn test_integer_operators() {
+        let test_arr = [" % ", "**", " & ", "|", " ^ ", "<<", " >> "];
+        for (i, input) in test_arr.iter().enumerate() {
+            let result = operator((*input).into());
+            assert!(result.is_ok(), "{}", input);
+
+            let (r, t) = result.unwrap();
+            assert!(r.is_empty(), "{}", input);
+            assert_eq!(39 + i, t.into());
+        }
+        assert!(multiplication_operator("**".into()).is_err());
+        assert!(bit_and_operator("&&".into()).is_err());
+        assert!(bit_or_operator("||".into()).is_err());
+    }
+
+    #[test]
+    fn test_logical_operators() {
+        let (r, t) = and_operator(" && b".into()).unwrap();
+        assert_eq!("b", *r.fragment());
+        assert_eq!(Token::AndOperator, t);
+        let (r, t) = or_operator("||b".into()).unwrap();
+        assert_eq!("b", *r.fragment());
+        assert_eq!(Token::OrOperator, t);
+        assert!(and_operator("&".into()).is_err());
+        assert!(or_operator("|".into()).is_err());
+    }
+
+    #[test]
+    fn test_parenthesized_is_carried_by_the_input() {
+        use crate::{expression_parsers::expression_parser, span::Input};
+
+        let (r, _) = expression_parser("1\n- 2".into()).unwrap();
+        assert_eq!("\n- 2", *r.fragment());
+        let (r, _) = expression_parser(Input::new_extra("1\n- 2", true)).unwrap();
+        assert!(r.is_empty());
+
+        // What follows the parentheses is outside them again
+        let (r, _) = parenthesized(expression_parser)("1\n- 2)\n- 3".into()).unwrap();
+        assert_eq!(")\n- 3", *r.fragment());
+        assert!(!r.extra);
+        let (r, _) = unparenthesized(expression_parser)(Input::new_extra("1\n- 2", true)).unwrap();
+        assert_eq!("\n- 2", *r.fragment());
+        assert!(r.extra);
+    }
 }
@@ -1,87 +1,440 @@
-use nom::{
-    branch::alt,
-    character::complete::{line_ending, multispace0},
-    combinator::{eof, map},
-    error::context,
-    multi::many1,
-    sequence::{preceded, terminated},
-    IResult,
+use nom::{combinator::map, error::context, multi::many1, sequence::terminated, InputTake};
+
+use crate::{
+    error::{location, string_literal_len, CompileError, Diagnostics, ParseError},
+    span::{Input, ParseResult, Span},
+    statement_parsers::{empty_statements, terminated_statement},
+    token::Token,
+    trivia_parsers::comment,
 };
 
-use crate::{expression_parsers::expression_parser, token::Token};
+/// Parses all of `source` into a `Token::Program`. `program_parser` stops at
+/// the first statement it cannot parse and gives back the rest; here that is
+/// an error, reported where the statement starts. A source with nothing but
+/// trivia and empty statements in it is an empty program.
+///
+/// Parsing does not stop at the first error. A statement that does not parse
+/// is skipped up to the next statement boundary, which is a newline or a `;`
+/// outside of any parentheses or braces, or a `}` that closes a block the
+/// statement is in, and replaced by a `Token::Error`. The `Diagnostics` then
+/// hold every error along with the rest of the program.
+///
+/// A `break` or `continue` outside of a loop, or a `return` outside of a
+/// function, is a syntax error as well, reported at its keyword.
+///
+/// # Example
+///
+/// ```
+/// use lrvmism::program_parsers::parse_program;
+/// use lrvmism::token::Token;
+/// assert!(parse_program("let x = 1\nx + 2\n").is_ok());
+/// assert!(parse_program("// nothing yet\n").unwrap().children().is_empty());
+/// let diagnostics = parse_program("1 + 2 @\nlet x = 3\nx * )").unwrap_err();
+/// assert_eq!(
+///     "parse error at 1:7: expected end of statement",
+///     diagnostics.errors[0].to_string()
+/// );
+/// assert_eq!(2, diagnostics.errors.len());
+/// let statements = diagnostics.program.children();
+/// assert!(matches!(statements[0], Token::Error { .. }));
+/// assert!(matches!(statements[1], Token::Let { .. }));
+/// assert_eq!(3, statements.len());
+/// ```
+pub fn parse_program(source: &str) -> Result<Token, Diagnostics> {
+    let mut statements = vec![];
+    let mut errors = vec![];
+    let mut rest = Input::from(source);
+    loop {
+        rest = match empty_statements(rest) {
+            Ok((after, _)) => after,
+            Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => {
+                errors.push(CompileError::from(e));
+                break;
+            }
+            Err(nom::Err::Incomplete(_)) => break,
+        };
+        if rest.is_empty() {
+            break;
+        }
+        match context("program_parser", terminated_statement)(rest) {
+            Ok((after, statement)) => {
+                errors.extend(
+                    statement
+                        .misplaced_control_flow()
+                        .into_iter()
+                        .map(|token| misplaced(source, token)),
+                );
+                statements.push(statement);
+                rest = after;
+            }
+            Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => {
+                errors.push(CompileError::from(e));
+                let (after, skipped) = skip_statement(rest);
+                let end = rest.take_split(skipped.trim_end().len()).0;
+                statements.push(Token::Error {
+                    span: Span::between(rest, end),
+                });
+                rest = after;
+            }
+            Err(nom::Err::Incomplete(_)) => {
+                let (line, column) = location(source, "");
+                errors.push(CompileError::Parse {
+                    line,
+                    column,
+                    message: "unexpected end of input".to_string(),
+                });
+                break;
+            }
+        }
+    }
+
+    let span = match (statements.first(), statements.last()) {
+        (Some(first), Some(last)) => first.span_to(last),
+        _ => Span::between(Input::from(source), Input::from(source)),
+    };
+    let program = Token::Program { statements, span };
+    if errors.is_empty() {
+        Ok(program)
+    } else {
+        Err(Diagnostics { errors, program })
+    }
+}
 
-pub fn program_parser(input: &str) -> IResult<&str, Token> {
+/// The error for the `break`, `continue` or `return` `token`, which is not
+/// in a loop or function it could leave
+fn misplaced(source: &str, token: &Token) -> CompileError {
+    let message = match token {
+        Token::Return { .. } => "`return` outside of a function".to_string(),
+        _ => format!("`{}` outside of a loop", token),
+    };
+    let span = token.span().expect("control flow is a node");
+    CompileError::Syntax(ParseError::at(source, span, message))
+}
+
+/// Skips a statement that does not parse, up to and including the `;` or
+/// `}` that ends it, or up to the newline that does. Comments and string
+/// literals are skipped whole so that nothing in them is taken for a
+/// boundary. Gives back the rest and what was skipped.
+fn skip_statement(input: Input) -> (Input, Input) {
+    let text = *input.fragment();
+    let (mut parentheses, mut braces) = (0, 0);
+    let mut offset = 0;
+    while offset < text.len() {
+        let rest = &text[offset..];
+        match comment(input.take_split(offset).0) {
+            Ok((after, _)) => {
+                offset = after.location_offset() - input.location_offset();
+                continue;
+            }
+            Err(nom::Err::Failure(_)) => return input.take_split(text.len()),
+            _ => {}
+        }
+        let c = rest.chars().next().expect("`rest` is not empty");
+        match c {
+            '"' => {
+                offset += string_literal_len(rest);
+                continue;
+            }
+            '(' => parentheses += 1,
+            ')' if parentheses > 0 => parentheses -= 1,
+            '{' => braces += 1,
+            '}' if braces > 0 => braces -= 1,
+            '}' => return input.take_split(offset + 1),
+            ';' if parentheses == 0 && braces == 0 => return input.take_split(offset + 1),
+            '\n' if parentheses == 0 && braces == 0 => break,
+            _ => {}
+        }
+        offset += c.len_utf8();
+    }
+    input.take_split(offset)
+}
+
+/// Parser for a whole program: one or more statements, each ended by a `;`
+/// or a newline. It stops before the first statement that does not parse.
+pub fn program_parser(input: Input) -> ParseResult<Token> {
     context(
         "program_parser",
-        preceded(
-            multispace0,
-            terminated(
-                map(many1(expression_parser), |expressions| Token::Program {
-                    expressions,
-                }),
-                alt((multispace0, line_ending, eof)),
-            ),
+        terminated(
+            map(many1(terminated_statement), |statements| Token::Program {
+                span: statements[0].span_to(&statements[statements.len() - 1]),
+                statements,
+            }),
+            empty_statements,
         ),
     )(input)
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::token::Token;
+    use crate::{error::CompileError, span::Span, token::Token, type_checker::Type};
+
+    use super::{parse_program, program_parser};
+
+    /// The span of `length` bytes from `offset` on the first line
+    fn at(offset: usize, length: usize) -> Span {
+        Span {
+            offset,
+            length,
+            line: 1,
+            column: offset + 1,
+        }
+    }
 
-    use super::program_parser;
     #[test]
     fn test_parse_program() {
         let test_program = "1+2";
 
-        let result = program_parser(test_program);
+        let result = program_parser(test_program.into());
         assert_eq!(result.is_ok(), true);
 
         let (r, program) = result.unwrap();
-        let expressions = vec![Token::Expression {
+        let statements = vec![Token::Expression {
             left: Box::new(Token::Term {
                 left: Box::new(Token::Factor {
-                    value: Box::new(Token::Integer { value: 1 }),
+                    value: Box::new(Token::Integer {
+                        value: 1,
+                        span: at(0, 1),
+                    }),
+                    span: at(0, 1),
                 }),
                 right: vec![],
+                span: at(0, 1),
             }),
             right: vec![(
                 Token::AdditionOperator,
                 Token::Term {
                     left: Box::new(Token::Factor {
-                        value: Box::new(Token::Integer { value: 2 }),
+                        value: Box::new(Token::Integer {
+                            value: 2,
+                            span: at(2, 1),
+                        }),
+                        span: at(2, 1),
                     }),
                     right: vec![],
+                    span: at(2, 1),
                 },
             )],
+            span: at(0, 3),
         }];
         assert!(r.is_empty());
-        assert_eq!(Token::Program { expressions }, program);
+        assert_eq!(
+            Token::Program {
+                statements,
+                span: at(0, 3),
+            },
+            program
+        );
     }
 
     #[test]
     fn test_parse_program_2() {
         let test_program = "3*4";
 
-        let result = program_parser(test_program);
+        let result = program_parser(test_program.into());
         assert_eq!(result.is_ok(), true);
 
         let (r, program) = result.unwrap();
-        let expressions = vec![Token::Expression {
+        let statements = vec![Token::Expression {
             left: Box::new(Token::Term {
                 left: Box::new(Token::Factor {
-                    value: Box::new(Token::Integer { value: 3 }),
+                    value: Box::new(Token::Integer {
+                        value: 3,
+                        span: at(0, 1),
+                    }),
+                    span: at(0, 1),
                 }),
                 right: vec![(
                     Token::MultiplicationOperator,
                     Token::Factor {
-                        value: Box::new(Token::Integer { value: 4 }),
+                        value: Box::new(Token::Integer {
+                            value: 4,
+                            span: at(2, 1),
+                        }),
+                        span: at(2, 1),
                     },
                 )],
+                span: at(0, 3),
             }),
             right: vec![],
+            span: at(0, 3),
         }];
         assert!(r.is_empty());
-        assert_eq!(Token::Program { expressions }, program);
+        assert_eq!(
+            Token::Program {
+                statements,
+                span: at(0, 3),
+            },
+            program
+        );
+    }
+
+    #[test]
+    fn test_program_spans() {
+        let source = "// setup\nlet x = (1 +\n  2)\n\nx * -x /* done */\n";
+        let (_, program) = program_parser(source.into()).unwrap();
+        assert_eq!(
+            Some(Span {
+                offset: 9,
+                length: 25,
+                line: 2,
+                column: 1,
+            }),
+            program.span()
+        );
+        let statements: Vec<Option<Span>> = program.children().iter().map(|s| s.span()).collect();
+        assert_eq!(
+            vec![
+                Some(Span {
+                    offset: 9,
+                    length: 17,
+                    line: 2,
+                    column: 1,
+                }),
+                Some(Span {
+                    offset: 28,
+                    length: 6,
+                    line: 5,
+                    column: 1,
+                }),
+            ],
+            statements
+        );
+    }
+
+    #[test]
+    fn test_parse_program_consumes_everything() {
+        for source in [
+            "1+2",
+            "1+2\n",
+            "1+2 // three\n",
+            "let x = 1\n\nx /* end */",
+            "1;;2",
+            "; 1;\n;",
+            "{ ; 1;; }",
+        ] {
+            assert!(parse_program(source).is_ok(), "{}", source);
+        }
+        // Nothing but trivia and empty statements is an empty program
+        for source in ["", "// only a comment\n", "\n\n", ";", " ; ;\n"] {
+            assert_eq!(
+                Ok(Token::Program {
+                    statements: vec![],
+                    span: at(0, 0),
+                }),
+                parse_program(source),
+                "{:?}",
+                source
+            );
+        }
+        for (source, line, column, expected) in [
+            ("1+2 )", 1, 5, "expected end of statement"),
+            ("1+2 @", 1, 5, "expected end of statement"),
+            ("1 2 3", 1, 3, "expected end of statement"),
+            ("let x = 1\nx\n  @ 2", 3, 3, "expected factor"),
+        ] {
+            let diagnostics = parse_program(source).unwrap_err();
+            assert_eq!(1, diagnostics.errors.len(), "{}", source);
+            match &diagnostics.errors[0] {
+                CompileError::Syntax(error) => {
+                    assert_eq!(
+                        (line, column, expected),
+                        (error.span.line, error.span.column, error.message.as_str()),
+                        "{}",
+                        source
+                    );
+                    assert_eq!(Some(&"program_parser"), error.context.first());
+                }
+                other => panic!("expected a syntax error for {:?}, got {:?}", source, other),
+            }
+        }
+        assert!(matches!(
+            parse_program("1\n2 + 99999999999999999999")
+                .unwrap_err()
+                .errors[..],
+            [CompileError::LiteralOutOfRange { ty: Type::Int, .. }]
+        ));
+    }
+
+    #[test]
+    fn test_parse_program_recovers_at_statement_boundaries() {
+        let source = "let x = )\nlet y = 2\nx @ y; y\nfn f() {\n  )\n}\ny";
+        let diagnostics = parse_program(source).unwrap_err();
+        let locations: Vec<(usize, usize, &str)> = diagnostics
+            .errors
+            .iter()
+            .map(|error| match error {
+                CompileError::Syntax(error) => {
+                    (error.span.line, error.span.column, error.message.as_str())
+                }
+                other => panic!("expected a syntax error, got {:?}", other),
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                (1, 9, "expected factor"),
+                (3, 3, "expected end of statement"),
+                (4, 9, "expected '}'")
+            ],
+            locations
+        );
+        let statements: Vec<(String, usize, usize)> = diagnostics
+            .program
+            .children()
+            .iter()
+            .map(|statement| {
+                let span = statement.span().unwrap();
+                (statement.to_string(), span.offset, span.length)
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                ("<error>".to_string(), 0, 9),
+                ("let y = 2".to_string(), 10, 9),
+                ("<error>".to_string(), 20, 6),
+                ("y".to_string(), 27, 1),
+                ("<error>".to_string(), 29, 14),
+                ("y".to_string(), 44, 1),
+            ],
+            statements
+        );
+        assert_eq!(
+            Some(at(0, source.len())),
+            diagnostics.program.span().map(|span| Span {
+                line: 1,
+                column: 1,
+                ..span
+            })
+        );
+    }
+
+    #[test]
+    fn test_recovery_skips_comments_and_strings() {
+        // The `;` in the string and the newline in the comment do not end
+        // the statement, the `;` after them does
+        let diagnostics = parse_program("1 @ /* a\n b */ \"x;\" ; 2 } 3").unwrap_err();
+        assert_eq!(2, diagnostics.errors.len());
+        let statements: Vec<String> = diagnostics
+            .program
+            .children()
+            .iter()
+            .map(|statement| statement.to_string())
+            .collect();
+        assert_eq!(vec!["<error>", "2", "<error>", "3"], statements);
+        assert_eq!(Some(at(0, 21)), diagnostics.program.children()[0].span());
+
+        // Nothing after a block comment that is never closed can be parsed
+        let diagnostics = parse_program("1 + /* open\n2").unwrap_err();
+        match &diagnostics.errors[..] {
+            [CompileError::Syntax(error)] => {
+                assert_eq!("unterminated block comment", error.message);
+                assert_eq!(
+                    (1, 5, 0),
+                    (error.span.line, error.span.column, error.span.length)
+                );
+                assert_eq!("1 + /* open", error.source_line);
+            }
+            other => panic!("expected an unterminated comment, got {:?}", other),
+        }
+        assert_eq!(1, diagnostics.program.children().len());
     }
 }
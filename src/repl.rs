@@ -0,0 +1,253 @@
+use std::io::{self, BufRead, Write};
+
+use lrvm::vm::VM;
+
+use crate::{
+    driver::{self, ExitState, RunResult},
+    error::{string_literal_len, CompileError},
+    span::Input,
+    token::Token,
+    trivia_parsers::comment,
+    type_checker::TypeChecker,
+    vistor::{Compiler, Visitor},
+};
+
+const COMMAND_PREFIX: char = ':';
+
+/// An interactive prompt that compiles each line it reads and runs it on a
+/// long-lived VM, so registers survive from one line to the next
+pub struct REPL {
+    /// The VM every line runs on
+    vm: VM,
+    /// The `Compiler` every line goes through, which keeps track of the
+    /// registers in use between lines
+    compiler: Compiler,
+    /// The `TypeChecker` every line goes through, which keeps track of the
+    /// types of the bindings between lines
+    checker: TypeChecker,
+    /// The tree of the last line that parsed, shown by `:ast`
+    last_program: Option<Token>,
+}
+
+impl Default for REPL {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl REPL {
+    pub fn new() -> Self {
+        REPL {
+            vm: VM::new(),
+            compiler: Compiler::new(),
+            checker: TypeChecker::new(),
+            last_program: None,
+        }
+    }
+
+    /// Reads lines from stdin until EOF or `:quit`
+    pub fn run(&mut self) {
+        println!("Welcome to lrvmism! Type :help for a list of commands.");
+        let stdin = io::stdin();
+        let mut lines = stdin.lock().lines();
+        // Lines of a statement that is still waiting for its closing braces
+        let mut buffer = String::new();
+        loop {
+            print!("{}", if buffer.is_empty() { ">>> " } else { "... " });
+            io::stdout().flush().expect("Unable to flush stdout");
+
+            let line = match lines.next() {
+                Some(Ok(line)) => line,
+                Some(Err(e)) => {
+                    eprintln!("Unable to read line: {}", e);
+                    continue;
+                }
+                None => break,
+            };
+            let line = line.trim();
+            if line.is_empty() && buffer.is_empty() {
+                continue;
+            }
+
+            if buffer.is_empty() {
+                if let Some(command) = line.strip_prefix(COMMAND_PREFIX) {
+                    if !self.execute_command(command) {
+                        break;
+                    }
+                    continue;
+                }
+            }
+
+            buffer.push_str(line);
+            buffer.push('\n');
+            if is_incomplete(&buffer) {
+                continue;
+            }
+            let source = std::mem::take(&mut buffer);
+
+            match self.eval(&source) {
+                Ok(result) => {
+                    if let Some(value) = result.value {
+                        println!("{}", value);
+                    }
+                    if let ExitState::Crashed { code } = result.exit {
+                        eprintln!("VM crashed with code {}", code);
+                    }
+                }
+                Err(CompileError::Diagnostics(diagnostics)) => eprintln!("{}", diagnostics),
+                Err(e) => eprintln!("error: {}", e),
+            }
+        }
+    }
+
+    /// Parses, compiles and runs `line` on the REPL's VM. A line that fails
+    /// to compile leaves no bindings or registers behind.
+    pub fn eval(&mut self, line: &str) -> Result<RunResult, CompileError> {
+        let program = driver::parse(line)?;
+        let checker = self.checker.clone();
+        let types = match driver::check(&mut self.checker, &program) {
+            Ok(types) => types,
+            Err(e) => {
+                self.checker = checker;
+                return Err(e);
+            }
+        };
+        let checkpoint = self.compiler.checkpoint();
+        self.compiler.clear_assembly();
+        self.compiler.set_types(types);
+        self.compiler.visit_token(&program);
+        self.last_program = Some(program);
+        let bytecode = match self.compiler.compile() {
+            Ok(b) => b,
+            Err(e) => {
+                self.compiler.restore(checkpoint);
+                self.checker = checker;
+                return Err(e);
+            }
+        };
+        Ok(driver::execute(
+            &mut self.vm,
+            bytecode,
+            self.compiler.result_register(),
+        ))
+    }
+
+    /// Runs a `:` meta-command. Returns `false` when the REPL should exit.
+    fn execute_command(&mut self, command: &str) -> bool {
+        match command {
+            "quit" | "q" => {
+                println!("Farewell! Have a great day!");
+                return false;
+            }
+            "help" | "h" => {
+                println!("Commands:");
+                println!("  :asm    show the assembly of the last line");
+                println!("  :ast    show the tree of the last line");
+                println!("  :regs   show the compiler's register allocation and the VM registers");
+                println!("  :reset  start over with a fresh VM and compiler");
+                println!("  :quit   exit the REPL");
+            }
+            "asm" => self.compiler.print_asm(),
+            "ast" => match &self.last_program {
+                Some(program) => println!("{:#?}", program),
+                None => println!("Nothing has been parsed yet"),
+            },
+            "regs" => {
+                self.compiler.print_used_registers();
+                self.compiler.print_free_registers();
+                self.compiler.print_free_float_registers();
+                println!("--------------------");
+                println!("|  VM Registers    |");
+                println!("--------------------");
+                println!("{:?}", self.vm.registers);
+            }
+            "reset" => {
+                *self = REPL::new();
+                println!("VM and compiler reset");
+            }
+            _ => println!("Unknown command: {}{}", COMMAND_PREFIX, command),
+        }
+        true
+    }
+}
+
+/// Whether a block or a block comment is still open at the end of
+/// `source`. Braces inside string literals and comments are skipped, the same
+/// way the parsers skip them.
+fn is_incomplete(source: &str) -> bool {
+    let mut depth = 0;
+    let mut rest = source;
+    while let Some(c) = rest.chars().next() {
+        let skipped = match c {
+            '"' => string_literal_len(rest),
+            '/' if rest.starts_with("//") || rest.starts_with("/*") => {
+                match comment(Input::from(rest)) {
+                    Ok((_, text)) => text.fragment().len(),
+                    Err(_) => return true,
+                }
+            }
+            '{' => {
+                depth += 1;
+                1
+            }
+            '}' => {
+                depth -= 1;
+                1
+            }
+            _ => c.len_utf8(),
+        };
+        rest = &rest[skipped..];
+    }
+    depth > 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_incomplete, REPL};
+    use crate::driver::Value;
+
+    #[test]
+    fn test_eval() {
+        let mut repl = REPL::new();
+        assert_eq!(Some(Value::Int(3)), repl.eval("1+2").unwrap().value);
+        assert_eq!(Some(Value::Int(20)), repl.eval("4*5").unwrap().value);
+        assert!(repl.eval(")").is_err());
+        assert_eq!(Some(Value::Int(1)), repl.eval("3-2").unwrap().value);
+    }
+
+    #[test]
+    fn test_bindings_survive_between_lines() {
+        let mut repl = REPL::new();
+        assert_eq!(None, repl.eval("let x = 6").unwrap().value);
+        assert_eq!(Some(Value::Int(42)), repl.eval("x * 7").unwrap().value);
+        assert!(repl.eval("let y = nope").is_err());
+        assert!(repl.eval("y").is_err());
+        assert_eq!(
+            Some(Value::Int(5)),
+            repl.eval("let y = 5\ny").unwrap().value
+        );
+    }
+
+    #[test]
+    fn test_is_incomplete() {
+        assert!(is_incomplete("if x > 1 {\n"));
+        assert!(!is_incomplete("if x > 1 {\n1\n}\n"));
+        assert!(!is_incomplete("1 + 2"));
+        assert!(!is_incomplete("print(\"{\")"));
+        assert!(!is_incomplete("print(\"\\\"{\")"));
+        assert!(!is_incomplete("1 // {"));
+        assert!(!is_incomplete("1 /* { */"));
+        assert!(is_incomplete("if x > 1 { // }\n"));
+        assert!(is_incomplete("if x > 1 {\nprint(\"}\")\n"));
+        assert!(is_incomplete("1 /* still open"));
+    }
+
+    #[test]
+    fn test_reset() {
+        let mut repl = REPL::new();
+        repl.eval("1+2").unwrap();
+        assert!(repl.execute_command("reset"));
+        assert!(repl.last_program.is_none());
+        assert!(!repl.execute_command("quit"));
+    }
+}
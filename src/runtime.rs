@@ -0,0 +1,350 @@
+//! Routines written in lrvm assembly that the `Compiler` links into programs
+//! which need them.
+//!
+//! A routine takes its argument in register 24, the first of
+//! `ARGUMENT_REGISTERS` (float register 24 for a float), and is entered with
+//! `CALL`. It only touches registers 24 to 31, integer and float, so the
+//! values a program keeps in the allocation pool survive the call without
+//! being saved.
+
+use std::collections::HashMap;
+
+/// A routine the `Compiler` can link into a program
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Routine {
+    /// Prints the digit 0 to 9 in $24
+    PrintDigit,
+    /// Prints the digits of `-$24`, where $24 is 0 or negative, padded with
+    /// leading zeros to at least $25 digits. Working on the negated value
+    /// lets the most negative integer be printed as well.
+    PrintPadded,
+    /// Prints the integer in $24
+    PrintInt,
+    /// Prints the float in float register 24, rounded to at most six
+    /// decimals and always with at least one, e.g. `-2.5` or `3.0`, or as
+    /// `inf`, `-inf` or `NaN`
+    PrintFloat,
+}
+
+/// Prints an integer: the sign, then the digits through `printpadded`
+const PRINT_INT: &str = "printint: NOP
+LOAD $31 #0
+LT $24 $31
+DJMPE @printintnegative
+SUB $31 $24 $24
+LOAD $31 @printintdigits
+JMP $31
+printintnegative: NOP
+PRTS @{minus}
+printintdigits: NOP
+LOAD $25 #1
+CALL @printpadded
+RET";
+
+/// Splits the value into digits with DIV and MUL, pushing them so they come
+/// back off the stack most significant first
+const PRINT_PADDED: &str = "printpadded: NOP
+LOAD $26 #0
+LOAD $27 #10
+printpaddedsplit: NOP
+DIV $24 $27 $28
+MUL $28 $27 $29
+SUB $29 $24 $29
+PUSH $29
+LOAD $31 #1
+ADD $26 $31 $26
+LOAD $31 #0
+ADD $28 $31 $24
+NEQ $24 $31
+DJMPE @printpaddedsplit
+LT $26 $25
+DJMPE @printpaddedsplit
+printpaddedemit: NOP
+POP $24
+CALL @printdigit
+LOAD $31 #1
+SUB $26 $31 $26
+LOAD $31 #0
+NEQ $26 $31
+DJMPE @printpaddedemit
+RET";
+
+/// lrvm cannot move a value between the float and integer registers, so a
+/// float is taken apart by counting how many times each power of ten can be
+/// subtracted from it. The six decimals are gathered into an integer, which
+/// loses its trailing zeros before `printpadded` prints it. NaN is the only
+/// value unequal to itself, and infinity the only other value `x` for which
+/// `x - x` is not 0; neither can be counted down, so both are printed by name.
+const PRINT_FLOAT: &str = "printfloat: NOP
+NEQF64 $24 $24
+DJMPE @printfloatnan
+LOADF64 $29 #0
+GTEF64 $24 $29
+DJMPE @printfloatpositive
+PRTS @{minus}
+SUBF64 $29 $24 $24
+printfloatpositive: NOP
+SUBF64 $24 $24 $25
+NEQF64 $25 $29
+DJMPE @printfloatinfinite
+LOADF64 $25 #5
+LOADF64 $26 #10000
+LOADF64 $27 #1000
+MULF64 $26 $27 $26
+DIVF64 $25 $26 $25
+ADDF64 $24 $25 $24
+LOADF64 $25 #1
+LOADF64 $26 #10
+printfloatscale: NOP
+MULF64 $25 $26 $27
+GTF64 $27 $24
+DJMPE @printfloatwhole
+ADDF64 $27 $29 $25
+LOAD $31 @printfloatscale
+JMP $31
+printfloatwhole: NOP
+LOAD $24 #0
+printfloatcount: NOP
+LTF64 $24 $25
+DJMPE @printfloatdigit
+SUBF64 $24 $25 $24
+LOAD $31 #1
+ADD $24 $31 $24
+LOAD $31 @printfloatcount
+JMP $31
+printfloatdigit: NOP
+CALL @printdigit
+DIVF64 $25 $26 $25
+LOADF64 $27 #1
+GTEF64 $25 $27
+DJMPE @printfloatwhole
+PRTS @{dot}
+LOAD $25 #6
+LOAD $26 #0
+printfloatfraction: NOP
+MULF64 $24 $26 $24
+LOAD $24 #0
+printfloatfractioncount: NOP
+LTF64 $24 $27
+DJMPE @printfloatfractiondigit
+SUBF64 $24 $27 $24
+LOAD $31 #1
+ADD $24 $31 $24
+LOAD $31 @printfloatfractioncount
+JMP $31
+printfloatfractiondigit: NOP
+LOAD $31 #10
+MUL $26 $31 $26
+ADD $26 $24 $26
+LOAD $31 #1
+SUB $25 $31 $25
+LOAD $31 #0
+NEQ $25 $31
+DJMPE @printfloatfraction
+LOAD $25 #6
+printfloattrim: NOP
+LOAD $31 #1
+EQ $25 $31
+DJMPE @printfloatdecimals
+LOAD $31 #10
+DIV $26 $31 $27
+MUL $27 $31 $28
+NEQ $28 $26
+DJMPE @printfloatdecimals
+LOAD $31 #0
+ADD $27 $31 $26
+LOAD $31 #1
+SUB $25 $31 $25
+LOAD $31 @printfloattrim
+JMP $31
+printfloatdecimals: NOP
+LOAD $31 #0
+SUB $31 $26 $24
+CALL @printpadded
+RET
+printfloatnan: NOP
+PRTS @{nan}
+RET
+printfloatinfinite: NOP
+PRTS @{inf}
+RET";
+
+impl Routine {
+    /// The label the routine starts at. Routine labels have no digits at
+    /// the end, so they never clash with the labels the `Compiler` numbers.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Routine::PrintDigit => "printdigit",
+            Routine::PrintPadded => "printpadded",
+            Routine::PrintInt => "printint",
+            Routine::PrintFloat => "printfloat",
+        }
+    }
+
+    /// The routines this one calls
+    pub fn dependencies(&self) -> &'static [Routine] {
+        match self {
+            Routine::PrintDigit => &[],
+            Routine::PrintPadded => &[Routine::PrintDigit],
+            Routine::PrintInt => &[Routine::PrintPadded],
+            Routine::PrintFloat => &[Routine::PrintDigit, Routine::PrintPadded],
+        }
+    }
+
+    /// The strings the routine prints, each with the name the routine's
+    /// assembly uses for its label
+    pub fn strings(&self) -> Vec<(String, String)> {
+        match self {
+            Routine::PrintDigit => (0..10)
+                .map(|digit| (format!("digit{}", digit), digit.to_string()))
+                .collect(),
+            Routine::PrintPadded => vec![],
+            Routine::PrintInt => vec![("minus".to_string(), "-".to_string())],
+            Routine::PrintFloat => vec![
+                ("minus".to_string(), "-".to_string()),
+                ("dot".to_string(), ".".to_string()),
+                ("inf".to_string(), "inf".to_string()),
+                ("nan".to_string(), "NaN".to_string()),
+            ],
+        }
+    }
+
+    /// The routine's assembly, with its strings referring to the labels in
+    /// `string_labels`, keyed by the names given by `strings`
+    pub fn assembly(&self, string_labels: &HashMap<String, String>) -> Vec<String> {
+        let mut source = match self {
+            Routine::PrintDigit => print_digit_source(),
+            Routine::PrintPadded => PRINT_PADDED.to_string(),
+            Routine::PrintInt => PRINT_INT.to_string(),
+            Routine::PrintFloat => PRINT_FLOAT.to_string(),
+        };
+        for (name, label) in string_labels {
+            source = source.replace(&format!("{{{}}}", name), label);
+        }
+        source.lines().map(str::to_string).collect()
+    }
+}
+
+/// There is no way to index into the `.data` section at run time, so the
+/// digit picks its string through a chain of comparisons
+fn print_digit_source() -> String {
+    let mut lines = vec!["printdigit: NOP".to_string()];
+    for digit in 0..10 {
+        lines.push(format!("LOAD $31 #{}", digit));
+        lines.push("EQ $24 $31".to_string());
+        lines.push(format!("DJMPE @printdigit{}", digit));
+    }
+    lines.push("RET".to_string());
+    for digit in 0..10 {
+        lines.push(format!("printdigit{}: NOP", digit));
+        lines.push(format!("PRTS @{{digit{}}}", digit));
+        lines.push("RET".to_string());
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use lrvm::{
+        assembler::Assembler,
+        vm::{VMEventType, VM},
+    };
+
+    use super::Routine;
+    use crate::vistor::asciiz;
+
+    /// Assembles `program` together with `routine` and everything it needs,
+    /// and runs it
+    fn run_with(routine: Routine, program: &str) -> Vec<VMEventType> {
+        let mut routines = vec![routine];
+        let mut i = 0;
+        while i < routines.len() {
+            for dependency in routines[i].dependencies() {
+                if !routines.contains(dependency) {
+                    routines.push(*dependency);
+                }
+            }
+            i += 1;
+        }
+
+        let mut data = vec![".data".to_string()];
+        let mut code = vec![".code".to_string(), program.to_string()];
+        let mut labels = HashMap::new();
+        for routine in &routines {
+            for (name, text) in routine.strings() {
+                if !labels.contains_key(&name) {
+                    data.push(asciiz(&name, &text));
+                    labels.insert(name.clone(), name);
+                }
+            }
+        }
+        for routine in &routines {
+            code.extend(routine.assembly(&labels));
+        }
+
+        let source = format!("{}\n{}", data.join("\n"), code.join("\n"));
+        let mut vm = VM::new();
+        vm.program = Assembler::new().assemble(&source).unwrap();
+        vm.run().into_iter().map(|e| e.event).collect()
+    }
+
+    #[test]
+    fn test_routines_assemble_and_return() {
+        for (routine, program) in [
+            (Routine::PrintInt, "LOAD $24 #0\nCALL @printint\nHLT"),
+            (
+                Routine::PrintInt,
+                "LOAD $0 #0\nLOAD $1 #12345\nSUB $0 $1 $24\nCALL @printint\nHLT",
+            ),
+            (
+                Routine::PrintFloat,
+                "LOADF64 $0 #0\nLOADF64 $1 #5\nLOADF64 $2 #2\nDIVF64 $1 $2 $1\nSUBF64 $0 $1 $24\nCALL @printfloat\nHLT",
+            ),
+            (Routine::PrintFloat, "LOADF64 $24 #1000\nCALL @printfloat\nHLT"),
+        ] {
+            let events = run_with(routine, program);
+            assert!(
+                matches!(events.last(), Some(VMEventType::GracefulStop { code: 0 })),
+                "{:?} {:?}",
+                routine,
+                events
+            );
+        }
+    }
+
+    #[test]
+    fn test_print_float_stops_on_non_finite_values() {
+        for dividend in [
+            "LOADF64 $0 #1",
+            "LOADF64 $0 #0",
+            "LOADF64 $0 #0\nLOADF64 $2 #1\nSUBF64 $0 $2 $0",
+        ] {
+            let program = format!(
+                "{}\nLOADF64 $1 #0\nDIVF64 $0 $1 $24\nCALL @printfloat\nHLT",
+                dividend
+            );
+            let events = run_with(Routine::PrintFloat, &program);
+            assert!(
+                matches!(events.last(), Some(VMEventType::GracefulStop { code: 0 })),
+                "{} {:?}",
+                program,
+                events
+            );
+        }
+    }
+
+    #[test]
+    fn test_routine_labels_have_no_trailing_digits() {
+        for routine in [
+            Routine::PrintDigit,
+            Routine::PrintPadded,
+            Routine::PrintInt,
+            Routine::PrintFloat,
+        ] {
+            assert!(routine.label().chars().all(|c| c.is_ascii_lowercase()));
+            assert!(routine.assembly(&HashMap::new())[0].starts_with(routine.label()));
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use nom::{error::VerboseError, IResult};
+use nom_locate::LocatedSpan;
+
+/// The input of every parser: a piece of the source that knows where in the
+/// source it is, and whether it is inside parentheses, where an infix
+/// operator may start a line. See `operator_parsers::parenthesized`.
+pub type Input<'a> = LocatedSpan<&'a str, bool>;
+
+/// What every parser gives back. Errors keep the whole chain of contexts the
+/// parser was in when it failed, which `ParseError` turns into a report.
+pub type ParseResult<'a, O> = IResult<Input<'a>, O, VerboseError<Input<'a>>>;
+
+/// Where a piece of the source is: its byte offset and length, and the
+/// 1-based line and column it starts at
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub offset: usize,
+    pub length: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Span {
+    /// The span of all of `input`
+    pub fn of(input: Input) -> Self {
+        Span {
+            offset: input.location_offset(),
+            length: input.fragment().len(),
+            line: input.location_line() as usize,
+            column: input.get_utf8_column(),
+        }
+    }
+
+    /// The span of what was consumed from `start` to get to `end`, which has
+    /// to be the rest of `start`
+    pub fn between(start: Input, end: Input) -> Self {
+        Span {
+            length: end.location_offset() - start.location_offset(),
+            ..Span::of(start)
+        }
+    }
+
+    /// This span stretched to the end of `end`, e.g. from the `-` of `-x` to
+    /// the `x`
+    pub fn to(self, end: Span) -> Self {
+        Span {
+            length: end.offset + end.length - self.offset,
+            ..self
+        }
+    }
+}
+
+/// Runs `parser` and gives back the span of what it consumed along with its
+/// output. `parser` should not skip trivia at its end, or the span would
+/// cover that as well.
+///
+/// # Example
+///
+/// ```
+/// use lrvmism::span::{spanned, Input, Span};
+/// use nom::{bytes::complete::tag, sequence::preceded, character::complete::multispace0};
+/// let (_reminder, (_, span)) = preceded(multispace0, spanned(tag("let")))(Input::from("\n  let x")).unwrap();
+/// assert_eq!(Span { offset: 3, length: 3, line: 2, column: 3 }, span);
+/// ```
+pub fn spanned<'a, O>(
+    mut parser: impl FnMut(Input<'a>) -> ParseResult<'a, O>,
+) -> impl FnMut(Input<'a>) -> ParseResult<'a, (O, Span)> {
+    move |input: Input<'a>| {
+        let (rest, output) = parser(input)?;
+        Ok((rest, (output, Span::between(input, rest))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nom::{bytes::complete::tag, sequence::pair};
+
+    use super::{spanned, Input, Span};
+
+    #[test]
+    fn test_spans() {
+        let source = Input::from("ab\ncdé f");
+        let (rest, ((first, second), span)) =
+            spanned(pair(tag("ab\nc"), tag("dé")))(source).unwrap();
+        assert_eq!(
+            Span {
+                offset: 0,
+                length: 7,
+                line: 1,
+                column: 1,
+            },
+            span
+        );
+        let second = Span::of(second);
+        assert_eq!((2, 2, 3), (second.line, second.column, second.length));
+        assert_eq!(
+            Span {
+                offset: 0,
+                length: 9,
+                line: 1,
+                column: 1,
+            },
+            Span::of(first).to(Span::of(rest))
+        );
+    }
+}
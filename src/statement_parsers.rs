@@ -0,0 +1,313 @@
+use nom::{
+    branch::alt,
+    character::complete::char,
+    combinator::{cut, eof, map, opt, peek, value},
+    error::context,
+    multi::many0,
+    sequence::{pair, preceded, terminated, tuple},
+};
+
+use crate::{
+    block_parsers::{block_parser, break_parser, continue_parser, while_parser},
+    expression_parsers::logical_parser,
+    factors_parsers::{annotation_parser, identifier, keyword},
+    function_parsers::{function_parser, return_parser},
+    operator_parsers::{assignment_operator, infix},
+    span::{spanned, Input, ParseResult},
+    token::Token,
+    trivia_parsers::{line_break, trivia},
+};
+
+/// Parser for a single statement of a program: a `let` binding, a `while`
+/// loop, `break`, `continue`, a function definition, `return`, a block, an
+/// assignment or an expression. What ends the statement is left to
+/// `end_of_statement`.
+pub fn statement_parser(input: Input) -> ParseResult<Token> {
+    context(
+        "statement_parser",
+        alt((
+            let_parser,
+            while_parser,
+            break_parser,
+            continue_parser,
+            function_parser,
+            return_parser,
+            block_parser,
+            expression_statement_parser,
+        )),
+    )(input)
+}
+
+/// Parser for a statement along with what ends it. Statements of a program
+/// or a block are parsed with this, so `1 2` is an error rather than two
+/// statements. Once the statement has parsed, anything but its end is a
+/// failure. Empty statements before it are skipped.
+///
+/// # Example
+///
+/// ```
+/// use lrvmism::statement_parsers::terminated_statement;
+/// let (reminder, _) = terminated_statement("x = 1; y = 2".into()).unwrap();
+/// assert_eq!("y = 2", *reminder.fragment());
+/// let (reminder, _) = terminated_statement("x = 1\n-y".into()).unwrap();
+/// assert_eq!("-y", *reminder.fragment());
+/// assert!(terminated_statement("x = 1 y = 2".into()).is_err());
+/// ```
+pub fn terminated_statement(input: Input) -> ParseResult<Token> {
+    preceded(
+        empty_statements,
+        terminated(statement_parser, cut(end_of_statement)),
+    )(input)
+}
+
+/// Parser for any number of empty statements, which are bare `;`s, along
+/// with the trivia around them. It never fails.
+///
+/// # Example
+///
+/// ```
+/// use lrvmism::statement_parsers::empty_statements;
+/// let (reminder, _) = empty_statements(" ;\n; // none\n 1".into()).unwrap();
+/// assert_eq!("1", *reminder.fragment());
+/// ```
+pub fn empty_statements(input: Input) -> ParseResult<()> {
+    value((), terminated(many0(preceded(trivia, char(';'))), trivia))(input)
+}
+
+/// Parser for what ends a statement: a `;`, or a newline, which means the
+/// next statement starts a line of its own. The `}` of the block the
+/// statement is in and the end of the input end it as well, but are left
+/// for the block and the program. A `;` right before either of them is
+/// allowed too.
+///
+/// Since a newline ends a statement, an infix operator cannot start a line,
+/// see `operator_parsers::infix`.
+pub fn end_of_statement(input: Input) -> ParseResult<()> {
+    context(
+        "end_of_statement",
+        alt((
+            preceded(
+                trivia,
+                alt((
+                    value((), terminated(char(';'), trivia)),
+                    value((), peek(char('}'))),
+                    value((), eof),
+                )),
+            ),
+            line_break,
+        )),
+    )(input)
+}
+
+/// Parser for an expression, optionally followed by an assignment operator
+/// and a value, e.g. `x + 1`, `x = 1` or `x += 2`. Any expression is accepted
+/// on the left of an assignment so that the `Compiler` can point out targets
+/// that cannot be assigned to, like `1 = 2`, instead of failing to parse.
+/// Parsing the expression only once keeps nested `if` expressions cheap.
+pub fn expression_statement_parser(input: Input) -> ParseResult<Token> {
+    context(
+        "expression_statement_parser",
+        map(
+            tuple((
+                logical_parser,
+                opt(tuple((infix(assignment_operator), logical_parser))),
+            )),
+            |(target, assignment)| match assignment {
+                Some((operator, value)) => Token::Assignment {
+                    span: target.span_to(&value),
+                    target: Box::new(target),
+                    operator: Box::new(operator),
+                    value: Box::new(value),
+                },
+                None => target,
+            },
+        ),
+    )(input)
+}
+
+/// Parser for a `let` binding, which names the value of an expression. The
+/// name can be annotated with a type, as in `let x: f64 = 1`.
+///
+/// # Example
+///
+/// ```
+/// use lrvmism::statement_parsers::let_parser;
+/// use lrvmism::token::Token;
+/// let (_reminder, token) = let_parser("let x = 1".into()).unwrap();
+/// match token {
+///     Token::Let { name, .. } => assert_eq!("x", name),
+///     other => panic!("expected a let, got {:?}", other),
+/// }
+/// ```
+pub fn let_parser(input: Input) -> ParseResult<Token> {
+    context(
+        "let_parser",
+        preceded(
+            trivia,
+            map(
+                pair(
+                    spanned(keyword("let")),
+                    cut(tuple((
+                        trivia,
+                        identifier,
+                        annotation_parser,
+                        trivia,
+                        char('='),
+                        logical_parser,
+                    ))),
+                ),
+                |((_, start), (_, name, annotation, _, _, value))| Token::Let {
+                    span: start.to(value.span().expect("values are nodes")),
+                    name: name.to_string(),
+                    annotation,
+                    value: Box::new(value),
+                },
+            ),
+        ),
+    )(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use nom::{character::complete::char, sequence::preceded};
+
+    use crate::{token::Token, type_checker::Type};
+
+    use super::{
+        end_of_statement, expression_statement_parser, let_parser, statement_parser,
+        terminated_statement,
+    };
+
+    #[test]
+    fn test_let_parser() {
+        let result = let_parser("let total = 1 + x".into());
+        assert!(result.is_ok());
+        let (r, token) = result.unwrap();
+        assert!(r.is_empty());
+        match token {
+            Token::Let {
+                name,
+                annotation,
+                value,
+                ..
+            } => {
+                assert_eq!("total", name);
+                assert_eq!(None, annotation);
+                assert_eq!("1 + x", value.to_string());
+            }
+            other => panic!("expected a let, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_let_annotation() {
+        for (input, expected) in [
+            ("let x: i32 = 3", Type::Int),
+            ("let x :f64= 3", Type::Float),
+            ("let ok: bool = 1 < 2", Type::Bool),
+            ("let s: str = \"s\"", Type::String),
+        ] {
+            let (r, token) = let_parser(input.into()).unwrap();
+            assert!(r.is_empty(), "{}", input);
+            assert!(
+                matches!(token, Token::Let { annotation: Some(ty), .. } if ty == expected),
+                "{}",
+                input
+            );
+        }
+        assert!(let_parser("let x: u8 = 3".into()).is_err());
+        // Ints are as wide as the 32-bit lrvm registers that hold them
+        assert!(let_parser("let x: i64 = 3".into()).is_err());
+        assert!(let_parser("let x: = 3".into()).is_err());
+    }
+
+    #[test]
+    fn test_let_needs_a_name() {
+        assert!(let_parser("let = 1".into()).is_err());
+        assert!(let_parser("let let = 1".into()).is_err());
+        assert!(let_parser("letx = 1".into()).is_err());
+    }
+
+    #[test]
+    fn test_expression_statement_parser() {
+        for (input, operator) in [
+            ("x = x + 1", Token::AssignOperator),
+            ("x += 2", Token::AddAssignOperator),
+            ("x -= 2", Token::SubtractAssignOperator),
+            ("x *= 2", Token::MultiplyAssignOperator),
+            ("x /= 2", Token::DivideAssignOperator),
+        ] {
+            let result = expression_statement_parser(input.into());
+            assert!(result.is_ok(), "{}", input);
+            let (r, token) = result.unwrap();
+            assert!(r.is_empty());
+            match token {
+                Token::Assignment {
+                    target,
+                    operator: parsed,
+                    ..
+                } => {
+                    assert_eq!(Some("x"), target.as_identifier());
+                    assert_eq!(operator, *parsed);
+                }
+                other => panic!("expected an assignment, got {:?}", other),
+            }
+        }
+        let (_, token) = expression_statement_parser("x + 1".into()).unwrap();
+        assert!(matches!(token, Token::Expression { .. }));
+    }
+
+    #[test]
+    fn test_statement_parser() {
+        let (_, token) = statement_parser("let x = 2".into()).unwrap();
+        assert!(matches!(token, Token::Let { .. }));
+        let (_, token) = statement_parser("lettuce * 2".into()).unwrap();
+        assert!(matches!(token, Token::Expression { .. }));
+        let (_, token) = statement_parser("1 = 2".into()).unwrap();
+        assert!(matches!(token, Token::Assignment { .. }));
+    }
+
+    #[test]
+    fn test_end_of_statement() {
+        let mut after_one = preceded(char('1'), end_of_statement);
+        for (input, rest) in [
+            ("1; 2", "2"),
+            ("1 }", "}"),
+            ("1 // one", ""),
+            ("1\n2", "2"),
+            ("1 /* a */\r\n  2", "2"),
+            // A block comment that spans lines ends the line it starts on
+            ("1 /* a\n b */ 2", "2"),
+        ] {
+            let (r, _) = after_one(input.into()).unwrap();
+            assert_eq!(rest, *r.fragment(), "{:?}", input);
+        }
+        assert!(after_one("1 2".into()).is_err());
+        assert!(after_one("1 /* a */ 2".into()).is_err());
+    }
+
+    #[test]
+    fn test_terminated_statement() {
+        let (r, token) = terminated_statement("{ let x = 1; x }\nx".into()).unwrap();
+        assert_eq!("x", *r.fragment());
+        assert!(matches!(token, Token::Block { ref statements, .. } if statements.len() == 2));
+
+        // A line that starts with a sign is a new statement
+        let (r, token) = terminated_statement("let x = 5\n-x".into()).unwrap();
+        assert_eq!("-x", *r.fragment());
+        assert_eq!("let x = 5", token.to_string());
+        let (r, token) = terminated_statement("let x = 5 -\n  x".into()).unwrap();
+        assert!(r.is_empty());
+        assert_eq!("let x = 5 - x", token.to_string());
+
+        // Empty statements are skipped
+        let (r, token) = terminated_statement("; ;x;; y".into()).unwrap();
+        assert_eq!("; y", *r.fragment());
+        assert_eq!("x", token.to_string());
+
+        match terminated_statement("1 2".into()) {
+            Err(nom::Err::Failure(_)) => {}
+            other => panic!("expected a failure, got {:?}", other),
+        }
+    }
+}
@@ -1,76 +1,240 @@
 use nom::{
     branch::alt,
-    character::complete::{line_ending, multispace0},
-    combinator::{eof, map},
+    combinator::{map, not, opt},
     error::context,
     multi::many0,
-    sequence::{preceded, terminated, tuple},
-    IResult,
+    sequence::{pair, preceded, terminated, tuple},
 };
 
 use crate::{
-    factors_parsers::factor_parser,
-    operator_parsers::{division_operator, multiplication_operator},
+    factors_parsers::{factor_parser, minimum_magnitude_parser},
+    operator_parsers::{
+        addition_operator, division_operator, infix, modulo_operator, multiplication_operator,
+        power_operator, substraction_operator,
+    },
+    span::{spanned, Input, ParseResult, Span},
     token::Token,
+    trivia_parsers::{trailing_trivia, trivia},
 };
 
 /// Looks for `Terms`. A `Term` consists of a `Factor` on the left,
-/// and then an `Operator` and `Factor` on the right.
+/// and then an `Operator` and `Factor` on the right. The operators are `*`,
+/// `/` and `%`, and a `Factor` may be raised to a power with `**` and have a
+/// sign in front.
 ///
 /// # Example
 ///
 /// ```
 /// use lrvmism::term_parsers::term_parser;
 /// fn test_parse_nested_term() {
-///     let result = term_parser("(3*4)*2");
+///     let result = term_parser("(3*4)*2".into());
 ///     assert_eq!(result.is_ok(), true);
 /// }
 /// ```
 ///
-pub fn term_parser(input: &str) -> IResult<&str, Token> {
+pub fn term_parser(input: Input) -> ParseResult<Token> {
     context(
         "term_parser",
         preceded(
-            multispace0,
+            trivia,
             terminated(
                 map(
                     tuple((
-                        factor_parser,
+                        unary_parser,
                         many0(tuple((
-                            alt((multiplication_operator, division_operator)),
-                            factor_parser,
+                            infix(alt((
+                                multiplication_operator,
+                                division_operator,
+                                modulo_operator,
+                            ))),
+                            unary_parser,
                         ))),
                     )),
                     |(left, right)| Token::Term {
+                        span: left.span_to(right.last().map_or(&left, |(_, last)| last)),
                         left: Box::new(left),
                         right,
                     },
                 ),
-                alt((multispace0, eof, line_ending)),
+                trailing_trivia,
             ),
         ),
     )(input)
 }
 
+/// Parser for the prefix operators `-` and `+`. They bind tighter than `*`
+/// but looser than `**`, so `-2 ** 2` is `-(2 ** 2)`. Without a prefix this
+/// is just the power.
+///
+/// Literals have no sign of their own, so a `-` after an operand is always
+/// the binary minus: `1 -2` is `1 - 2`. After a prefix `-`, and unless it is
+/// raised to a power, the literal may be `2147483648`, so that `i32::MIN`
+/// can be written as `-2147483648`.
+///
+/// # Example
+///
+/// ```
+/// use lrvmism::term_parsers::unary_parser;
+/// use lrvmism::token::Token;
+/// let (_reminder, token) = unary_parser("-(1 + 2)".into()).unwrap();
+/// assert!(matches!(token, Token::Negation { .. }));
+/// let (_reminder, token) = unary_parser("+x".into()).unwrap();
+/// assert!(matches!(token, Token::Identity { .. }));
+/// ```
+pub fn unary_parser(input: Input) -> ParseResult<Token> {
+    context(
+        "unary_parser",
+        alt((
+            map(
+                pair(
+                    prefix(substraction_operator),
+                    terminated(
+                        minimum_magnitude_parser,
+                        not(preceded(trivia, power_operator)),
+                    ),
+                ),
+                |(start, value)| Token::Negation {
+                    span: start.to(value.span().expect("operands are nodes")),
+                    value: Box::new(value),
+                },
+            ),
+            map(
+                pair(prefix(substraction_operator), unary_parser),
+                |(start, value)| Token::Negation {
+                    span: start.to(value.span().expect("operands are nodes")),
+                    value: Box::new(value),
+                },
+            ),
+            map(
+                pair(prefix(addition_operator), unary_parser),
+                |(start, value)| Token::Identity {
+                    span: start.to(value.span().expect("operands are nodes")),
+                    value: Box::new(value),
+                },
+            ),
+            power_parser,
+        )),
+    )(input)
+}
+
+/// Runs the prefix operator `parser` and gives back a span that starts at
+/// the operator, to be stretched over the operand with `Span::to`
+fn prefix<'a>(
+    parser: impl FnMut(Input<'a>) -> ParseResult<'a, Token>,
+) -> impl FnMut(Input<'a>) -> ParseResult<'a, Span> {
+    map(preceded(trivia, spanned(parser)), |(_, span)| span)
+}
+
+/// Parser for `**`, which binds tighter than the other arithmetic operators
+/// and is right-associative: `2 ** 3 ** 2` is `2 ** (3 ** 2)`. The exponent
+/// may have a sign, as in `2 ** -1`. Without `**` this is just the `Factor`.
+///
+/// # Example
+///
+/// ```
+/// use lrvmism::term_parsers::power_parser;
+/// use lrvmism::token::Token;
+/// let (_reminder, token) = power_parser("2 ** 3 ** 2".into()).unwrap();
+/// match token {
+///     Token::Power { exponent, .. } => assert_eq!("3 ** 2", exponent.to_string()),
+///     other => panic!("expected a power, got {:?}", other),
+/// }
+/// ```
+pub fn power_parser(input: Input) -> ParseResult<Token> {
+    context(
+        "power_parser",
+        map(
+            tuple((
+                factor_parser,
+                opt(preceded(infix(power_operator), unary_parser)),
+            )),
+            |(base, exponent)| match exponent {
+                Some(exponent) => Token::Power {
+                    span: base.span_to(&exponent),
+                    base: Box::new(base),
+                    exponent: Box::new(exponent),
+                },
+                None => base,
+            },
+        ),
+    )(input)
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{term_parsers::term_parser, token::Token};
 
     #[test]
     fn test_parse_term() {
-        let result = term_parser("3*4");
+        let result = term_parser("3*4".into());
         assert_eq!(result.is_ok(), true);
     }
 
     #[test]
     fn test_parse_nested_term() {
-        let result = term_parser("(3*4)*2");
+        let result = term_parser("(3*4)*2".into());
         assert_eq!(result.is_ok(), true);
     }
 
     #[test]
     fn test_parse_really_nested_term() {
-        let result = term_parser("((3*4)*2)");
+        let result = term_parser("((3*4)*2)".into());
         assert_eq!(result.is_ok(), true);
     }
+
+    #[test]
+    fn test_power_binds_tighter_than_term_operators() {
+        let (r, token) = term_parser("2 * 3 ** 2 % 5".into()).unwrap();
+        assert!(r.is_empty());
+        match token {
+            Token::Term { left, right, .. } => {
+                assert_eq!("2", left.to_string());
+                assert_eq!(Token::MultiplicationOperator, right[0].0);
+                assert!(matches!(right[0].1, Token::Power { .. }));
+                assert_eq!(Token::ModuloOperator, right[1].0);
+            }
+            other => panic!("expected a term, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_unary_operators() {
+        for (input, expected) in [
+            ("-x", "-x"),
+            ("- -2", "- -2"),
+            ("+2 * -3", "+2 * -3"),
+            ("-2 ** 2", "-2 ** 2"),
+            ("2 ** -1", "2 ** -1"),
+            ("-2147483648", "-2147483648"),
+            ("- 2_147_483_648 * 1", "-2147483648 * 1"),
+        ] {
+            let (r, token) = term_parser(input.into()).unwrap();
+            assert!(r.is_empty(), "{}", input);
+            assert_eq!(expected, token.to_string());
+        }
+
+        // `**` binds tighter than the sign
+        let (_, token) = term_parser("-2 ** 2".into()).unwrap();
+        match token {
+            Token::Term { left, .. } => match *left {
+                Token::Negation { value, .. } => assert!(matches!(*value, Token::Power { .. })),
+                other => panic!("expected a negation, got {:?}", other),
+            },
+            other => panic!("expected a term, got {:?}", other),
+        }
+
+        // The magnitude of i32::MIN is only a literal right after a `-`
+        for input in [
+            "2147483648",
+            "2 * 2147483648",
+            "-2147483648 ** 1",
+            "-(2147483648)",
+        ] {
+            assert!(
+                matches!(term_parser(input.into()), Err(nom::Err::Failure(_))),
+                "{}",
+                input
+            );
+        }
+    }
 }
@@ -1,4 +1,11 @@
-#[derive(Debug, PartialEq)]
+use std::fmt;
+
+use crate::{span::Span, type_checker::Type};
+
+/// A node of the tree the parsers build. Every node carries the `Span` of
+/// the source it was parsed from; the operators do not, as they are only
+/// ever part of another node.
+#[derive(Debug, Clone, PartialEq)]
 pub enum Token {
     AdditionOperator,
     SubtractionOperator,
@@ -6,48 +13,398 @@ pub enum Token {
     DivisionOperator,
     Integer {
         value: i64,
+        span: Span,
     },
     Float {
         value: f64,
+        span: Span,
     },
     Factor {
         value: Box<Token>,
+        span: Span,
     },
     Term {
         left: Box<Token>,
         right: Vec<(Token, Token)>,
+        span: Span,
     },
     Expression {
         left: Box<Token>,
         right: Vec<(Token, Token)>,
+        span: Span,
     },
+    /// The statements of a whole source, in order
     Program {
-        expressions: Vec<Token>,
+        statements: Vec<Token>,
+        span: Span,
+    },
+    Identifier {
+        name: String,
+        span: Span,
+    },
+    /// `let name: annotation = value`, the annotation being optional
+    Let {
+        name: String,
+        annotation: Option<Type>,
+        value: Box<Token>,
+        span: Span,
+    },
+    AssignOperator,
+    AddAssignOperator,
+    SubtractAssignOperator,
+    MultiplyAssignOperator,
+    DivideAssignOperator,
+    Assignment {
+        target: Box<Token>,
+        operator: Box<Token>,
+        value: Box<Token>,
+        span: Span,
+    },
+    EqualOperator,
+    NotEqualOperator,
+    GreaterThanOperator,
+    LessThanOperator,
+    GreaterThanOrEqualOperator,
+    LessThanOrEqualOperator,
+    Comparison {
+        left: Box<Token>,
+        operator: Box<Token>,
+        right: Box<Token>,
+        span: Span,
+    },
+    Block {
+        statements: Vec<Token>,
+        span: Span,
+    },
+    If {
+        condition: Box<Token>,
+        body: Box<Token>,
+        alternative: Option<Box<Token>>,
+        span: Span,
+    },
+    While {
+        condition: Box<Token>,
+        body: Box<Token>,
+        span: Span,
+    },
+    Break {
+        span: Span,
+    },
+    Continue {
+        span: Span,
+    },
+    /// A function definition. Each parameter is a name with an optional
+    /// type annotation, and the return type is optional as well.
+    Function {
+        name: String,
+        parameters: Vec<(String, Option<Type>)>,
+        return_type: Option<Type>,
+        body: Box<Token>,
+        span: Span,
+    },
+    Return {
+        value: Option<Box<Token>>,
+        span: Span,
+    },
+    Call {
+        name: String,
+        arguments: Vec<Token>,
+        span: Span,
+    },
+    /// A string literal, with its escapes already resolved
+    String {
+        value: String,
+        span: Span,
+    },
+    Boolean {
+        value: bool,
+        span: Span,
+    },
+    AndOperator,
+    OrOperator,
+    /// `left && right` or `left || right`. The right side is only evaluated
+    /// when the left one does not decide the result.
+    Logical {
+        left: Box<Token>,
+        operator: Box<Token>,
+        right: Box<Token>,
+        span: Span,
+    },
+    /// `!value`
+    Not {
+        value: Box<Token>,
+        span: Span,
+    },
+    ModuloOperator,
+    PowerOperator,
+    BitAndOperator,
+    BitOrOperator,
+    BitXorOperator,
+    ShiftLeftOperator,
+    ShiftRightOperator,
+    /// `base ** exponent`. `exponent` may be a `Power` in turn, as `**` is
+    /// right-associative.
+    Power {
+        base: Box<Token>,
+        exponent: Box<Token>,
+        span: Span,
+    },
+    /// `left <operator> right` for one of the integer operators `&`, `|`,
+    /// `^`, `<<` and `>>`
+    Bitwise {
+        left: Box<Token>,
+        operator: Box<Token>,
+        right: Box<Token>,
+        span: Span,
+    },
+    /// `-value`
+    Negation {
+        value: Box<Token>,
+        span: Span,
+    },
+    /// `+value`, which is `value` itself
+    Identity {
+        value: Box<Token>,
+        span: Span,
+    },
+    /// A statement that did not parse. It takes the place of the statement
+    /// so that the rest of the program still has a tree.
+    Error {
+        span: Span,
     },
 }
 
 impl From<usize> for Token {
     fn from(value: usize) -> Self {
         let default_add = Box::new(Token::AdditionOperator);
-        let default_vec = vec![(Token::Integer { value: 0 }, Token::Integer { value: 0 })];
+        let default_vec = vec![(
+            Token::Integer {
+                value: 0,
+                span: Span::default(),
+            },
+            Token::Integer {
+                value: 0,
+                span: Span::default(),
+            },
+        )];
         match value {
             0 => Token::AdditionOperator,
             1 => Token::SubtractionOperator,
             2 => Token::MultiplicationOperator,
             3 => Token::DivisionOperator,
-            4 => Token::Integer { value: 0 },
-            5 => Token::Float { value: 0.0 },
-            6 => Token::Factor { value: default_add },
+            4 => Token::Integer {
+                value: 0,
+                span: Span::default(),
+            },
+            5 => Token::Float {
+                value: 0.0,
+                span: Span::default(),
+            },
+            6 => Token::Factor {
+                value: default_add,
+                span: Span::default(),
+            },
             7 => Token::Term {
                 left: default_add,
                 right: default_vec,
+                span: Span::default(),
             },
             8 => Token::Expression {
-                left: Box::new(Token::Integer { value: 0 }),
-                right: vec![(Token::Integer { value: 0 }, Token::Integer { value: 0 })],
+                left: Box::new(Token::Integer {
+                    value: 0,
+                    span: Span::default(),
+                }),
+                right: vec![(
+                    Token::Integer {
+                        value: 0,
+                        span: Span::default(),
+                    },
+                    Token::Integer {
+                        value: 0,
+                        span: Span::default(),
+                    },
+                )],
+                span: Span::default(),
             },
             9 => Token::Program {
-                expressions: vec![],
+                statements: vec![],
+                span: Span::default(),
+            },
+            10 => Token::Identifier {
+                name: String::new(),
+                span: Span::default(),
+            },
+            11 => Token::Let {
+                name: String::new(),
+                annotation: None,
+                value: Box::new(Token::Integer {
+                    value: 0,
+                    span: Span::default(),
+                }),
+                span: Span::default(),
+            },
+            12 => Token::AssignOperator,
+            13 => Token::AddAssignOperator,
+            14 => Token::SubtractAssignOperator,
+            15 => Token::MultiplyAssignOperator,
+            16 => Token::DivideAssignOperator,
+            17 => Token::Assignment {
+                target: Box::new(Token::Identifier {
+                    name: String::new(),
+                    span: Span::default(),
+                }),
+                operator: Box::new(Token::AssignOperator),
+                value: Box::new(Token::Integer {
+                    value: 0,
+                    span: Span::default(),
+                }),
+                span: Span::default(),
+            },
+            18 => Token::EqualOperator,
+            19 => Token::NotEqualOperator,
+            20 => Token::GreaterThanOperator,
+            21 => Token::LessThanOperator,
+            22 => Token::GreaterThanOrEqualOperator,
+            23 => Token::LessThanOrEqualOperator,
+            24 => Token::Comparison {
+                left: Box::new(Token::Integer {
+                    value: 0,
+                    span: Span::default(),
+                }),
+                operator: Box::new(Token::EqualOperator),
+                right: Box::new(Token::Integer {
+                    value: 0,
+                    span: Span::default(),
+                }),
+                span: Span::default(),
+            },
+            25 => Token::Block {
+                statements: vec![],
+                span: Span::default(),
+            },
+            26 => Token::If {
+                condition: Box::new(Token::Integer {
+                    value: 0,
+                    span: Span::default(),
+                }),
+                body: Box::new(Token::Block {
+                    statements: vec![],
+                    span: Span::default(),
+                }),
+                alternative: None,
+                span: Span::default(),
+            },
+            27 => Token::While {
+                condition: Box::new(Token::Integer {
+                    value: 0,
+                    span: Span::default(),
+                }),
+                body: Box::new(Token::Block {
+                    statements: vec![],
+                    span: Span::default(),
+                }),
+                span: Span::default(),
+            },
+            28 => Token::Break {
+                span: Span::default(),
+            },
+            29 => Token::Continue {
+                span: Span::default(),
+            },
+            30 => Token::Function {
+                name: String::new(),
+                parameters: vec![],
+                return_type: None,
+                body: Box::new(Token::Block {
+                    statements: vec![],
+                    span: Span::default(),
+                }),
+                span: Span::default(),
+            },
+            31 => Token::Return {
+                value: None,
+                span: Span::default(),
+            },
+            32 => Token::Call {
+                name: String::new(),
+                arguments: vec![],
+                span: Span::default(),
+            },
+            33 => Token::String {
+                value: String::new(),
+                span: Span::default(),
+            },
+            34 => Token::Boolean {
+                value: false,
+                span: Span::default(),
+            },
+            35 => Token::AndOperator,
+            36 => Token::OrOperator,
+            37 => Token::Logical {
+                left: Box::new(Token::Boolean {
+                    value: false,
+                    span: Span::default(),
+                }),
+                operator: Box::new(Token::AndOperator),
+                right: Box::new(Token::Boolean {
+                    value: false,
+                    span: Span::default(),
+                }),
+                span: Span::default(),
+            },
+            38 => Token::Not {
+                value: Box::new(Token::Boolean {
+                    value: false,
+                    span: Span::default(),
+                }),
+                span: Span::default(),
+            },
+            39 => Token::ModuloOperator,
+            40 => Token::PowerOperator,
+            41 => Token::BitAndOperator,
+            42 => Token::BitOrOperator,
+            43 => Token::BitXorOperator,
+            44 => Token::ShiftLeftOperator,
+            45 => Token::ShiftRightOperator,
+            46 => Token::Power {
+                base: Box::new(Token::Integer {
+                    value: 0,
+                    span: Span::default(),
+                }),
+                exponent: Box::new(Token::Integer {
+                    value: 0,
+                    span: Span::default(),
+                }),
+                span: Span::default(),
+            },
+            47 => Token::Bitwise {
+                left: Box::new(Token::Integer {
+                    value: 0,
+                    span: Span::default(),
+                }),
+                operator: Box::new(Token::BitAndOperator),
+                right: Box::new(Token::Integer {
+                    value: 0,
+                    span: Span::default(),
+                }),
+                span: Span::default(),
+            },
+            48 => Token::Negation {
+                value: Box::new(Token::Integer {
+                    value: 0,
+                    span: Span::default(),
+                }),
+                span: Span::default(),
+            },
+            49 => Token::Identity {
+                value: Box::new(Token::Integer {
+                    value: 0,
+                    span: Span::default(),
+                }),
+                span: Span::default(),
+            },
+            50 => Token::Error {
+                span: Span::default(),
             },
             _ => panic!("invalid token"),
         }
@@ -61,12 +418,711 @@ impl Into<usize> for Token {
             Token::SubtractionOperator => 1,
             Token::MultiplicationOperator => 2,
             Token::DivisionOperator => 3,
-            Token::Integer { value } => 4,
-            Token::Float { value } => 5,
-            Token::Factor { value } => 6,
-            Token::Term { left, right } => 7,
-            Token::Expression { left, right } => 8,
-            Token::Program { expressions } => 9,
+            Token::Integer { .. } => 4,
+            Token::Float { .. } => 5,
+            Token::Factor { .. } => 6,
+            Token::Term { .. } => 7,
+            Token::Expression { .. } => 8,
+            Token::Program { .. } => 9,
+            Token::Identifier { .. } => 10,
+            Token::Let { .. } => 11,
+            Token::AssignOperator => 12,
+            Token::AddAssignOperator => 13,
+            Token::SubtractAssignOperator => 14,
+            Token::MultiplyAssignOperator => 15,
+            Token::DivideAssignOperator => 16,
+            Token::Assignment { .. } => 17,
+            Token::EqualOperator => 18,
+            Token::NotEqualOperator => 19,
+            Token::GreaterThanOperator => 20,
+            Token::LessThanOperator => 21,
+            Token::GreaterThanOrEqualOperator => 22,
+            Token::LessThanOrEqualOperator => 23,
+            Token::Comparison { .. } => 24,
+            Token::Block { .. } => 25,
+            Token::If { .. } => 26,
+            Token::While { .. } => 27,
+            Token::Break { .. } => 28,
+            Token::Continue { .. } => 29,
+            Token::Function { .. } => 30,
+            Token::Return { .. } => 31,
+            Token::Call { .. } => 32,
+            Token::String { .. } => 33,
+            Token::Boolean { .. } => 34,
+            Token::AndOperator => 35,
+            Token::OrOperator => 36,
+            Token::Logical { .. } => 37,
+            Token::Not { .. } => 38,
+            Token::ModuloOperator => 39,
+            Token::PowerOperator => 40,
+            Token::BitAndOperator => 41,
+            Token::BitOrOperator => 42,
+            Token::BitXorOperator => 43,
+            Token::ShiftLeftOperator => 44,
+            Token::ShiftRightOperator => 45,
+            Token::Power { .. } => 46,
+            Token::Bitwise { .. } => 47,
+            Token::Negation { .. } => 48,
+            Token::Identity { .. } => 49,
+            Token::Error { .. } => 50,
+        }
+    }
+}
+
+impl Token {
+    /// Where this token is in the source, or `None` for an operator, which
+    /// is only ever part of another node
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Token::Integer { span, .. }
+            | Token::Float { span, .. }
+            | Token::Factor { span, .. }
+            | Token::Term { span, .. }
+            | Token::Expression { span, .. }
+            | Token::Program { span, .. }
+            | Token::Identifier { span, .. }
+            | Token::Let { span, .. }
+            | Token::Assignment { span, .. }
+            | Token::Comparison { span, .. }
+            | Token::Block { span, .. }
+            | Token::If { span, .. }
+            | Token::While { span, .. }
+            | Token::Break { span, .. }
+            | Token::Continue { span, .. }
+            | Token::Function { span, .. }
+            | Token::Return { span, .. }
+            | Token::Call { span, .. }
+            | Token::String { span, .. }
+            | Token::Boolean { span, .. }
+            | Token::Logical { span, .. }
+            | Token::Not { span, .. }
+            | Token::Power { span, .. }
+            | Token::Bitwise { span, .. }
+            | Token::Negation { span, .. }
+            | Token::Identity { span, .. }
+            | Token::Error { span } => Some(*span),
+            _ => None,
+        }
+    }
+
+    /// The span from the start of this node to the end of `last`, for a node
+    /// that is made of the two and what is between them. Neither can be an
+    /// operator.
+    pub fn span_to(&self, last: &Token) -> Span {
+        let (first, last) = (self.span(), last.span());
+        first
+            .zip(last)
+            .map(|(first, last)| first.to(last))
+            .expect("operators have no span")
+    }
+
+    /// The name this token refers to when it is nothing but an identifier,
+    /// possibly wrapped in single-operand terms, factors and parentheses.
+    /// This is what can appear on the left of an assignment.
+    pub fn as_identifier(&self) -> Option<&str> {
+        match self.unwrapped() {
+            Token::Identifier { name, .. } => Some(name),
+            _ => None,
+        }
+    }
+
+    /// The text of this token when it is nothing but a string literal, seen
+    /// through the same wrappers as `as_identifier`
+    pub fn as_string(&self) -> Option<&str> {
+        match self.unwrapped() {
+            Token::String { value, .. } => Some(value),
+            _ => None,
+        }
+    }
+
+    /// This token without any single-operand terms, factors and parentheses
+    /// around it
+    pub fn unwrapped(&self) -> &Token {
+        match self {
+            Token::Factor { value, .. } => value.unwrapped(),
+            Token::Term { left, right, .. } | Token::Expression { left, right, .. }
+                if right.is_empty() =>
+            {
+                left.unwrapped()
+            }
+            _ => self,
+        }
+    }
+
+    /// Every `break` or `continue` that is not inside a `while` loop, and
+    /// `return` that is not inside a function, in source order. A function
+    /// body starts outside of any loop, even if the function is defined in
+    /// one.
+    pub fn misplaced_control_flow(&self) -> Vec<&Token> {
+        let mut misplaced = vec![];
+        self.find_misplaced_control_flow(false, false, &mut misplaced);
+        misplaced
+    }
+
+    fn find_misplaced_control_flow<'a>(
+        &'a self,
+        in_loop: bool,
+        in_function: bool,
+        misplaced: &mut Vec<&'a Token>,
+    ) {
+        match self {
+            Token::Break { .. } | Token::Continue { .. } if !in_loop => misplaced.push(self),
+            Token::Return { .. } if !in_function => {
+                misplaced.push(self);
+                for child in self.children() {
+                    child.find_misplaced_control_flow(in_loop, in_function, misplaced);
+                }
+            }
+            Token::While {
+                condition, body, ..
+            } => {
+                condition.find_misplaced_control_flow(in_loop, in_function, misplaced);
+                body.find_misplaced_control_flow(true, in_function, misplaced);
+            }
+            Token::Function { body, .. } => {
+                body.find_misplaced_control_flow(false, true, misplaced)
+            }
+            _ => {
+                for child in self.children() {
+                    child.find_misplaced_control_flow(in_loop, in_function, misplaced);
+                }
+            }
+        }
+    }
+
+    /// The tokens directly below this one in the tree, in source order
+    pub fn children(&self) -> Vec<&Token> {
+        match self {
+            Token::Factor { value, .. }
+            | Token::Not { value, .. }
+            | Token::Negation { value, .. }
+            | Token::Identity { value, .. } => vec![value],
+            Token::Term { left, right, .. } | Token::Expression { left, right, .. } => {
+                let mut children = vec![left.as_ref()];
+                for (operator, operand) in right {
+                    children.push(operator);
+                    children.push(operand);
+                }
+                children
+            }
+            Token::Program { statements, .. } | Token::Block { statements, .. } => {
+                statements.iter().collect()
+            }
+            Token::Let { value, .. } => vec![value],
+            Token::Assignment {
+                target: left,
+                operator,
+                value: right,
+                ..
+            }
+            | Token::Comparison {
+                left,
+                operator,
+                right,
+                ..
+            }
+            | Token::Logical {
+                left,
+                operator,
+                right,
+                ..
+            }
+            | Token::Bitwise {
+                left,
+                operator,
+                right,
+                ..
+            } => vec![left, operator, right],
+            Token::Power { base, exponent, .. } => vec![base, exponent],
+            Token::If {
+                condition,
+                body,
+                alternative,
+                ..
+            } => {
+                let mut children = vec![condition.as_ref(), body.as_ref()];
+                if let Some(alternative) = alternative {
+                    children.push(alternative);
+                }
+                children
+            }
+            Token::While {
+                condition, body, ..
+            } => vec![condition, body],
+            Token::Function { body, .. } => vec![body],
+            Token::Return { value, .. } => value.iter().map(|value| value.as_ref()).collect(),
+            Token::Call { arguments, .. } => arguments.iter().collect(),
+            _ => vec![],
+        }
+    }
+
+    /// Whether this token has to be wrapped in parentheses to be used as a
+    /// `Factor`
+    pub fn needs_parentheses(&self) -> bool {
+        !matches!(
+            self,
+            Token::Integer { .. }
+                | Token::Float { .. }
+                | Token::Identifier { .. }
+                | Token::Factor { .. }
+                | Token::If { .. }
+                | Token::Call { .. }
+                | Token::String { .. }
+                | Token::Boolean { .. }
+                | Token::Not { .. }
+        )
+    }
+
+    /// Flattens the tree back into its source-order lexemes, e.g. `(1+2)*3`
+    /// becomes `(`, `1`, `+`, `2`, `)`, `*`, `3`.
+    pub fn lexemes(&self) -> Vec<String> {
+        let mut lexemes = vec![];
+        self.push_lexemes(&mut lexemes);
+        lexemes
+    }
+
+    fn push_lexemes(&self, lexemes: &mut Vec<String>) {
+        match self {
+            Token::Factor { value, .. } => {
+                if value.needs_parentheses() {
+                    lexemes.push("(".to_string());
+                    value.push_lexemes(lexemes);
+                    lexemes.push(")".to_string());
+                } else {
+                    value.push_lexemes(lexemes);
+                }
+            }
+            Token::Term { left, right, .. } | Token::Expression { left, right, .. } => {
+                left.push_lexemes(lexemes);
+                for (operator, operand) in right {
+                    operator.push_lexemes(lexemes);
+                    operand.push_lexemes(lexemes);
+                }
+            }
+            Token::Program { statements, .. } => {
+                for statement in statements {
+                    statement.push_lexemes(lexemes);
+                }
+            }
+            Token::Block { statements, .. } => {
+                lexemes.push("{".to_string());
+                for statement in statements {
+                    statement.push_lexemes(lexemes);
+                }
+                lexemes.push("}".to_string());
+            }
+            Token::If {
+                condition,
+                body,
+                alternative,
+                ..
+            } => {
+                lexemes.push("if".to_string());
+                condition.push_lexemes(lexemes);
+                body.push_lexemes(lexemes);
+                if let Some(alternative) = alternative {
+                    lexemes.push("else".to_string());
+                    alternative.push_lexemes(lexemes);
+                }
+            }
+            Token::While {
+                condition, body, ..
+            } => {
+                lexemes.push("while".to_string());
+                condition.push_lexemes(lexemes);
+                body.push_lexemes(lexemes);
+            }
+            Token::Function {
+                name,
+                parameters,
+                return_type,
+                body,
+                ..
+            } => {
+                lexemes.push("fn".to_string());
+                lexemes.push(name.clone());
+                lexemes.push("(".to_string());
+                for (i, (parameter, annotation)) in parameters.iter().enumerate() {
+                    if i > 0 {
+                        lexemes.push(",".to_string());
+                    }
+                    lexemes.push(parameter.clone());
+                    if let Some(annotation) = annotation {
+                        lexemes.push(":".to_string());
+                        lexemes.push(annotation.to_string());
+                    }
+                }
+                lexemes.push(")".to_string());
+                if let Some(return_type) = return_type {
+                    lexemes.push("->".to_string());
+                    lexemes.push(return_type.to_string());
+                }
+                body.push_lexemes(lexemes);
+            }
+            Token::Not { value, .. } => {
+                lexemes.push("!".to_string());
+                value.push_lexemes(lexemes);
+            }
+            Token::Negation { value, .. } => {
+                lexemes.push("-".to_string());
+                value.push_lexemes(lexemes);
+            }
+            Token::Identity { value, .. } => {
+                lexemes.push("+".to_string());
+                value.push_lexemes(lexemes);
+            }
+            Token::Power { base, exponent, .. } => {
+                base.push_lexemes(lexemes);
+                lexemes.push("**".to_string());
+                exponent.push_lexemes(lexemes);
+            }
+            Token::Return { value, .. } => {
+                lexemes.push("return".to_string());
+                if let Some(value) = value {
+                    value.push_lexemes(lexemes);
+                }
+            }
+            Token::Call {
+                name, arguments, ..
+            } => {
+                lexemes.push(name.clone());
+                lexemes.push("(".to_string());
+                for (i, argument) in arguments.iter().enumerate() {
+                    if i > 0 {
+                        lexemes.push(",".to_string());
+                    }
+                    argument.push_lexemes(lexemes);
+                }
+                lexemes.push(")".to_string());
+            }
+            Token::Let {
+                name,
+                annotation,
+                value,
+                ..
+            } => {
+                lexemes.push("let".to_string());
+                lexemes.push(name.clone());
+                if let Some(annotation) = annotation {
+                    lexemes.push(":".to_string());
+                    lexemes.push(annotation.to_string());
+                }
+                lexemes.push("=".to_string());
+                value.push_lexemes(lexemes);
+            }
+            Token::Assignment {
+                target: left,
+                operator,
+                value: right,
+                ..
+            }
+            | Token::Comparison {
+                left,
+                operator,
+                right,
+                ..
+            }
+            | Token::Logical {
+                left,
+                operator,
+                right,
+                ..
+            }
+            | Token::Bitwise {
+                left,
+                operator,
+                right,
+                ..
+            } => {
+                left.push_lexemes(lexemes);
+                operator.push_lexemes(lexemes);
+                right.push_lexemes(lexemes);
+            }
+            leaf => lexemes.push(leaf.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::AdditionOperator => write!(f, "+"),
+            Token::SubtractionOperator => write!(f, "-"),
+            Token::MultiplicationOperator => write!(f, "*"),
+            Token::DivisionOperator => write!(f, "/"),
+            Token::Integer { value, .. } => write!(f, "{}", value),
+            Token::Float { value, .. } => write!(f, "{:?}", value),
+            Token::Factor { value, .. } => {
+                if value.needs_parentheses() {
+                    write!(f, "({})", value)
+                } else {
+                    write!(f, "{}", value)
+                }
+            }
+            Token::Term { left, right, .. } | Token::Expression { left, right, .. } => {
+                write!(f, "{}", left)?;
+                for (operator, operand) in right {
+                    write!(f, " {} {}", operator, operand)?;
+                }
+                Ok(())
+            }
+            Token::Identifier { name, .. } => write!(f, "{}", name),
+            Token::Let {
+                name,
+                annotation,
+                value,
+                ..
+            } => match annotation {
+                Some(annotation) => write!(f, "let {}: {} = {}", name, annotation, value),
+                None => write!(f, "let {} = {}", name, value),
+            },
+            Token::AssignOperator => write!(f, "="),
+            Token::AddAssignOperator => write!(f, "+="),
+            Token::SubtractAssignOperator => write!(f, "-="),
+            Token::MultiplyAssignOperator => write!(f, "*="),
+            Token::DivideAssignOperator => write!(f, "/="),
+            Token::Assignment {
+                target: left,
+                operator,
+                value: right,
+                ..
+            }
+            | Token::Comparison {
+                left,
+                operator,
+                right,
+                ..
+            }
+            | Token::Logical {
+                left,
+                operator,
+                right,
+                ..
+            }
+            | Token::Bitwise {
+                left,
+                operator,
+                right,
+                ..
+            } => write!(f, "{} {} {}", left, operator, right),
+            Token::Power { base, exponent, .. } => write!(f, "{} ** {}", base, exponent),
+            Token::Block { statements, .. } => {
+                write!(f, "{{")?;
+                for (i, statement) in statements.iter().enumerate() {
+                    if i > 0 {
+                        writeln!(f)?;
+                    }
+                    write!(f, " {}", statement)?;
+                }
+                write!(f, " }}")
+            }
+            Token::If {
+                condition,
+                body,
+                alternative,
+                ..
+            } => {
+                write!(f, "if {} {}", condition, body)?;
+                match alternative {
+                    Some(alternative) => write!(f, " else {}", alternative),
+                    None => Ok(()),
+                }
+            }
+            Token::While {
+                condition, body, ..
+            } => write!(f, "while {} {}", condition, body),
+            Token::Break { .. } => write!(f, "break"),
+            Token::Continue { .. } => write!(f, "continue"),
+            Token::Function {
+                name,
+                parameters,
+                return_type,
+                body,
+                ..
+            } => {
+                let parameters: Vec<String> = parameters
+                    .iter()
+                    .map(|(parameter, annotation)| match annotation {
+                        Some(annotation) => format!("{}: {}", parameter, annotation),
+                        None => parameter.clone(),
+                    })
+                    .collect();
+                write!(f, "fn {}({})", name, parameters.join(", "))?;
+                if let Some(return_type) = return_type {
+                    write!(f, " -> {}", return_type)?;
+                }
+                write!(f, " {}", body)
+            }
+            Token::Return { value, .. } => match value {
+                Some(value) => write!(f, "return {}", value),
+                None => write!(f, "return"),
+            },
+            Token::String { value, .. } => {
+                let escaped = value
+                    .replace('\\', "\\\\")
+                    .replace('"', "\\\"")
+                    .replace('\n', "\\n")
+                    .replace('\t', "\\t");
+                write!(f, "\"{}\"", escaped)
+            }
+            Token::Call {
+                name, arguments, ..
+            } => {
+                let arguments: Vec<String> = arguments.iter().map(|a| a.to_string()).collect();
+                write!(f, "{}({})", name, arguments.join(", "))
+            }
+            Token::EqualOperator => write!(f, "=="),
+            Token::NotEqualOperator => write!(f, "!="),
+            Token::GreaterThanOperator => write!(f, ">"),
+            Token::LessThanOperator => write!(f, "<"),
+            Token::GreaterThanOrEqualOperator => write!(f, ">="),
+            Token::LessThanOrEqualOperator => write!(f, "<="),
+            Token::Boolean { value, .. } => write!(f, "{}", value),
+            Token::AndOperator => write!(f, "&&"),
+            Token::OrOperator => write!(f, "||"),
+            Token::Not { value, .. } => write!(f, "!{}", value),
+            Token::Negation { value, .. } => write!(f, "-{}", unary_operand(value)),
+            Token::Identity { value, .. } => write!(f, "+{}", unary_operand(value)),
+            Token::Error { .. } => write!(f, "<error>"),
+            Token::ModuloOperator => write!(f, "%"),
+            Token::PowerOperator => write!(f, "**"),
+            Token::BitAndOperator => write!(f, "&"),
+            Token::BitOrOperator => write!(f, "|"),
+            Token::BitXorOperator => write!(f, "^"),
+            Token::ShiftLeftOperator => write!(f, "<<"),
+            Token::ShiftRightOperator => write!(f, ">>"),
+            Token::Program { statements, .. } => {
+                for (i, statement) in statements.iter().enumerate() {
+                    if i > 0 {
+                        writeln!(f)?;
+                    }
+                    write!(f, "{}", statement)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// The operand of a unary `-` or `+` as it is displayed. One that starts
+/// with a sign of its own is set apart by a space, as `--2` does not parse.
+fn unary_operand(value: &Token) -> String {
+    let operand = value.to_string();
+    if operand.starts_with(['-', '+']) {
+        format!(" {}", operand)
+    } else {
+        operand
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::program_parsers::program_parser;
+
+    use super::Token;
+
+    #[test]
+    fn test_lexemes() {
+        let (_, program) = program_parser("(1+2)*3".into()).unwrap();
+        assert_eq!(vec!["(", "1", "+", "2", ")", "*", "3"], program.lexemes());
+    }
+
+    #[test]
+    fn test_as_identifier() {
+        for (source, expected) in [
+            ("x", Some("x")),
+            ("(x)", Some("x")),
+            ("x+1", None),
+            ("1", None),
+        ] {
+            let (_, program) = program_parser(source.into()).unwrap();
+            match program {
+                Token::Program { statements, .. } => {
+                    assert_eq!(expected, statements[0].as_identifier(), "{}", source)
+                }
+                other => panic!("expected a program, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_misplaced_control_flow() {
+        for (source, misplaced) in [
+            ("while 1 { break }", None),
+            ("while 1 { if 1 { continue } }", None),
+            ("break", Some("break")),
+            ("while 1 { 1 }\nif 1 { continue }", Some("continue")),
+            ("fn f() { while 1 { return 1 } }", None),
+            ("return 1", Some("return 1")),
+            ("while 1 { fn f() { break } }", Some("break")),
+        ] {
+            let (_, program) = program_parser(source.into()).unwrap();
+            assert_eq!(
+                misplaced,
+                program
+                    .misplaced_control_flow()
+                    .first()
+                    .map(|token| token.to_string())
+                    .as_deref(),
+                "{}",
+                source
+            );
+        }
+
+        let (_, program) = program_parser(
+            "break
+while 1 { continue }
+if 1 { continue }
+return"
+                .into(),
+        )
+        .unwrap();
+        let misplaced: Vec<String> = program
+            .misplaced_control_flow()
+            .into_iter()
+            .map(|token| token.to_string())
+            .collect();
+        assert_eq!(vec!["break", "continue", "return"], misplaced);
+    }
+
+    #[test]
+    fn test_display() {
+        let (_, program) = program_parser("(1+2)*3 - 4/2".into()).unwrap();
+        assert_eq!("(1 + 2) * 3 - 4 / 2", program.to_string());
+        let (_, program) = program_parser("(1 < 2) + 3 >= 4".into()).unwrap();
+        assert_eq!("(1 < 2) + 3 >= 4", program.to_string());
+        let (_, program) = program_parser("if a > b { a } else { b }".into()).unwrap();
+        assert_eq!("if a > b { a } else { b }", program.to_string());
+        let (_, program) = program_parser(r#"print("a\t\"b\"\n")"#.into()).unwrap();
+        assert_eq!(r#"print("a\t\"b\"\n")"#, program.to_string());
+        let (_, program) = program_parser("let x:f64=1.5".into()).unwrap();
+        assert_eq!("let x: f64 = 1.5", program.to_string());
+        let (_, program) = program_parser("fn f(a:f64, b) -> f64 { a }".into()).unwrap();
+        assert_eq!("fn f(a: f64, b) -> f64 { a }", program.to_string());
+        let (_, program) = program_parser("!(a<b) || true&&!c".into()).unwrap();
+        assert_eq!("!(a < b) || true && !c", program.to_string());
+        let (_, program) = program_parser("a|b^c&d<<1 == 2**3**2%5".into()).unwrap();
+        assert_eq!("a | b ^ c & d << 1 == 2 ** 3 ** 2 % 5", program.to_string());
+        let (_, program) = program_parser("-(1+2) * +x - -2**2".into()).unwrap();
+        assert_eq!("-(1 + 2) * +x - -2 ** 2", program.to_string());
+    }
+
+    #[test]
+    fn test_display_round_trips_signs() {
+        for (source, expected) in [
+            ("- -2", "- -2"),
+            ("-(-2)", "-(-2)"),
+            ("+ -x", "+ -x"),
+            ("- +2", "- +2"),
+            ("1 - - - x", "1 - - -x"),
+            ("-2 ** - -3", "-2 ** - -3"),
+        ] {
+            let (r, program) = program_parser(source.into()).unwrap();
+            assert!(r.is_empty(), "{}", source);
+            assert_eq!(expected, program.to_string(), "{}", source);
+            let (r, reparsed) = program_parser(expected.into()).unwrap();
+            assert!(r.is_empty(), "{}", expected);
+            assert_eq!(expected, reparsed.to_string(), "{}", source);
         }
     }
 }
@@ -0,0 +1,215 @@
+use nom::{
+    branch::alt,
+    bytes::complete::{tag, take_till},
+    character::complete::{multispace1, space1},
+    combinator::{recognize, value, verify},
+    error::{context, ErrorKind, ParseError, VerboseError},
+    multi::many0_count,
+    sequence::preceded,
+    InputTake,
+};
+
+use crate::span::{Input, ParseResult};
+
+/// Skips everything between tokens that means nothing to the program:
+/// whitespace, `//` line comments and `/* */` block comments. Gives back what
+/// was skipped, which may be nothing.
+///
+/// # Example
+///
+/// ```
+/// use lrvmism::trivia_parsers::trivia;
+/// let (reminder, skipped) = trivia("  // one\n /* two */ 3".into()).unwrap();
+/// assert_eq!("3", *reminder.fragment());
+/// assert_eq!("  // one\n /* two */ ", *skipped.fragment());
+/// ```
+pub fn trivia(input: Input) -> ParseResult<Input> {
+    context(
+        "trivia",
+        recognize(many0_count(alt((multispace1, comment)))),
+    )(input)
+}
+
+/// Skips the trivia after a token, up to the end of its line: spaces, tabs
+/// and comments that do not span lines. Every parser ends with this, so the
+/// trivia that crosses a newline is left for whatever comes next, which can
+/// tell from it whether a line ended.
+///
+/// # Example
+///
+/// ```
+/// use lrvmism::trivia_parsers::trailing_trivia;
+/// let (reminder, _) = trailing_trivia(" /* a */ // b\n2".into()).unwrap();
+/// assert_eq!("\n2", *reminder.fragment());
+/// let (reminder, _) = trailing_trivia(" /* a\n */ 2".into()).unwrap();
+/// assert_eq!("/* a\n */ 2", *reminder.fragment());
+/// ```
+pub fn trailing_trivia(input: Input) -> ParseResult<Input> {
+    context(
+        "trivia",
+        recognize(many0_count(alt((
+            space1,
+            verify(comment, |comment: &Input| !comment.contains('\n')),
+        )))),
+    )(input)
+}
+
+/// Parser for a single comment, either a line or a block comment. The whole
+/// comment is given back, delimiters included, so a formatter can keep it.
+pub fn comment(input: Input) -> ParseResult<Input> {
+    context("comment", alt((line_comment, block_comment)))(input)
+}
+
+/// Parser for a `//` comment, which runs until the end of the line. The line
+/// ending itself is not part of the comment.
+pub fn line_comment(input: Input) -> ParseResult<Input> {
+    context(
+        "line_comment",
+        recognize(preceded(tag("//"), take_till(|c| c == '\n' || c == '\r'))),
+    )(input)
+}
+
+/// Parser for a `/* */` comment. Block comments nest, so commenting out code
+/// that already has a block comment in it works: `/* a /* b */ c */` is one
+/// comment. A comment that is never closed is a failure.
+///
+/// # Example
+///
+/// ```
+/// use lrvmism::trivia_parsers::block_comment;
+/// let (reminder, comment) = block_comment("/* a /* b */ c */ 1".into()).unwrap();
+/// assert_eq!(" 1", *reminder.fragment());
+/// assert_eq!("/* a /* b */ c */", *comment.fragment());
+/// assert!(block_comment("/* never closed".into()).is_err());
+/// ```
+pub fn block_comment(input: Input) -> ParseResult<Input> {
+    let text = *input.fragment();
+    if !text.starts_with("/*") {
+        return Err(nom::Err::Error(VerboseError::from_error_kind(
+            input,
+            ErrorKind::Tag,
+        )));
+    }
+    let mut depth = 0;
+    let mut offset = 0;
+    while offset < text.len() {
+        let rest = &text[offset..];
+        if rest.starts_with("/*") {
+            depth += 1;
+            offset += 2;
+        } else if rest.starts_with("*/") {
+            depth -= 1;
+            offset += 2;
+            if depth == 0 {
+                return Ok(input.take_split(offset));
+            }
+        } else {
+            offset += rest.chars().next().map_or(1, char::len_utf8);
+        }
+    }
+    // Nothing after an unclosed comment can be parsed, so there is no point
+    // in trying anything else
+    Err(nom::Err::Failure(VerboseError::from_error_kind(
+        input,
+        ErrorKind::TakeUntil,
+    )))
+}
+
+/// Skips trivia that crosses a newline, which is how a line ends. A block
+/// comment that spans lines ends one too. A newline is never a token of its
+/// own; as every parser stops its trailing trivia at the end of the line,
+/// this sees the newline after the last token of the line.
+///
+/// # Example
+///
+/// ```
+/// use lrvmism::trivia_parsers::line_break;
+/// let (reminder, _) = line_break("// a\n 2".into()).unwrap();
+/// assert_eq!("2", *reminder.fragment());
+/// assert!(line_break(" /* a */ 2".into()).is_err());
+/// ```
+pub fn line_break(input: Input) -> ParseResult<()> {
+    value((), verify(trivia, |skipped: &Input| skipped.contains('\n')))(input)
+}
+
+/// Skips trivia, failing if that crosses a newline. Whatever comes next has
+/// to be on the same line as what came before.
+///
+/// # Example
+///
+/// ```
+/// use lrvmism::trivia_parsers::same_line;
+/// let (reminder, _) = same_line(" /* a */ - 2".into()).unwrap();
+/// assert_eq!("- 2", *reminder.fragment());
+/// assert!(same_line(" // a\n- 2".into()).is_err());
+/// assert!(same_line(" /* a\n */ - 2".into()).is_err());
+/// ```
+pub fn same_line(input: Input) -> ParseResult<()> {
+    value(
+        (),
+        verify(trivia, |skipped: &Input| !skipped.contains('\n')),
+    )(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use nom::error::{ErrorKind, VerboseErrorKind};
+
+    use super::{block_comment, comment, line_comment, trivia};
+
+    #[test]
+    fn test_line_comment() {
+        let (r, comment) = line_comment("// a comment\n1".into()).unwrap();
+        assert_eq!("\n1", *r.fragment());
+        assert_eq!("// a comment", *comment.fragment());
+        let (r, comment) = line_comment("/// doc".into()).unwrap();
+        assert!(r.is_empty());
+        assert_eq!("/// doc", *comment.fragment());
+        assert!(line_comment("/ 2".into()).is_err());
+    }
+
+    #[test]
+    fn test_block_comment() {
+        for input in [
+            "/**/",
+            "/* a */",
+            "/* /* */ */",
+            "/* a\n * b\n */",
+            "/* ** */",
+        ] {
+            let (r, comment) = block_comment(input.into()).unwrap();
+            assert!(r.is_empty(), "{}", input);
+            assert_eq!(input, *comment.fragment());
+        }
+        assert!(block_comment("/* /* */".into()).is_err());
+        assert!(block_comment("/ * */".into()).is_err());
+    }
+
+    #[test]
+    fn test_trivia() {
+        for (input, rest, skipped) in [
+            ("1", "1", ""),
+            ("", "", ""),
+            (
+                "\t/* a */ // b\r\n  /* c /* d */ */\n1",
+                "1",
+                "\t/* a */ // b\r\n  /* c /* d */ */\n",
+            ),
+            // A division is not a comment
+            (" / 2", "/ 2", " "),
+        ] {
+            let (r, s) = trivia(input.into()).unwrap();
+            assert_eq!((rest, skipped), (*r.fragment(), *s.fragment()), "{}", input);
+        }
+        match trivia(" /* open".into()) {
+            Err(nom::Err::Failure(e)) => {
+                let (comment, kind) = &e.errors[0];
+                assert_eq!(1, comment.location_offset());
+                assert_eq!(&VerboseErrorKind::Nom(ErrorKind::TakeUntil), kind);
+            }
+            other => panic!("expected a failure, got {:?}", other),
+        }
+        let (_, c) = comment("/* é */".into()).unwrap();
+        assert_eq!("/* é */", *c.fragment());
+    }
+}
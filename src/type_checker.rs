@@ -0,0 +1,1079 @@
+//! A pass between parsing and code generation that infers the type of every
+//! expression, so that mixing integers and floats is caught with a location
+//! before any assembly is emitted.
+//!
+//! Errors are located by the `Span` of the node they are about, e.g. all of
+//! `1 + 2.5` for the `+` that cannot add an int to a float.
+
+use std::{collections::HashMap, fmt};
+
+use crate::{span::Span, token::Token, vistor::BUILTINS};
+
+/// The type of a value. It displays as the name annotations use for it.
+/// What is not annotated otherwise is an `i32`, hence the default. lrvm
+/// registers are 32 bits wide, so that is the width of an int.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Type {
+    #[default]
+    Int,
+    Float,
+    /// `true`, `false`, or the result of a comparison or a logical
+    /// operator. Bools are kept in integer registers as 1
+    /// or 0, and are coerced into the int 1 or 0 wherever an int is expected,
+    /// so `(a < b) + 1` is an int. An `if` or `while` takes an integer
+    /// condition as well.
+    Bool,
+    String,
+    /// What a statement without a value, like `let` or `while`, gives
+    Unit,
+}
+
+impl Type {
+    /// Whether a value of this type can stand where an integer is expected.
+    /// Bools are 1 or 0, so they can.
+    fn is_integral(&self) -> bool {
+        matches!(self, Type::Int | Type::Bool)
+    }
+
+    /// Whether a value of type `found` can be used where this type is
+    /// expected. No value at all is never accepted: `()` only stands for 0
+    /// where nothing was asked for, in an unannotated `let` or function.
+    fn accepts(&self, found: Type) -> bool {
+        *self == found || *self == Type::Int && found == Type::Bool
+    }
+
+    /// The type of this value as an operand of arithmetic or an ordering,
+    /// which turns bools into ints
+    fn as_number(self) -> Option<Type> {
+        match self {
+            Type::Int | Type::Bool => Some(Type::Int),
+            Type::Float => Some(Type::Float),
+            Type::String | Type::Unit => None,
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Int => write!(f, "i32"),
+            Type::Float => write!(f, "f64"),
+            Type::Bool => write!(f, "bool"),
+            Type::String => write!(f, "str"),
+            Type::Unit => write!(f, "()"),
+        }
+    }
+}
+
+/// A type error, without its location
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypeError {
+    /// The two sides of an operator have different types. lrvm keeps
+    /// integers and floats in separate registers and cannot convert between
+    /// them, so there is no coercing one into the other.
+    MismatchedOperands {
+        operator: String,
+        left: Type,
+        right: Type,
+    },
+    /// An operator was used on a type it does not work on, e.g. `"a" * 2`
+    UnsupportedOperand { operator: String, found: Type },
+    /// The branches of an `if` give values of different types
+    MismatchedBranches { body: Type, alternative: Type },
+    /// An `if` or `while` condition is not an integer or a bool
+    InvalidCondition { found: Type },
+    /// An argument does not have the type of its parameter, which is `i32`
+    /// unless annotated otherwise
+    InvalidArgument {
+        name: String,
+        expected: Type,
+        found: Type,
+    },
+    /// A function gives back a value of another type than its return type,
+    /// which is `i32` unless annotated otherwise
+    InvalidReturn { expected: Type, found: Type },
+    /// The value of an annotated `let` does not have the annotated type
+    MismatchedAnnotation { expected: Type, found: Type },
+    /// The exponent of `**` is not an integer
+    InvalidExponent { found: Type },
+}
+
+impl fmt::Display for TypeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TypeError::MismatchedOperands {
+                operator,
+                left,
+                right,
+            } => write!(
+                f,
+                "mismatched types: cannot apply `{}` to {} and {}",
+                operator, left, right
+            ),
+            TypeError::UnsupportedOperand { operator, found } => {
+                write!(f, "cannot apply `{}` to {}", operator, found)
+            }
+            TypeError::MismatchedBranches { body, alternative } => write!(
+                f,
+                "`if` and `else` have different types: {} and {}",
+                body, alternative
+            ),
+            TypeError::InvalidCondition { found } => {
+                write!(f, "expected an i32 or bool condition, found {}", found)
+            }
+            TypeError::InvalidArgument {
+                name,
+                expected,
+                found,
+            } => write!(
+                f,
+                "mismatched types: argument of `{}` should be {}, found {}",
+                name, expected, found
+            ),
+            TypeError::InvalidReturn { expected, found } => write!(
+                f,
+                "mismatched types: expected to return {}, found {}",
+                expected, found
+            ),
+            TypeError::MismatchedAnnotation { expected, found } => write!(
+                f,
+                "mismatched types: expected {}, found {}",
+                expected, found
+            ),
+            TypeError::InvalidExponent { found } => {
+                write!(f, "the exponent of `**` should be i32, found {}", found)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TypeError {}
+
+/// The type the `TypeChecker` inferred for each node of a tree. Nodes are
+/// keyed by the offset and length of their span, so the table is only good
+/// for the source it was built from. A node shares its span only with the
+/// nodes wrapped around it without an operator, like the factor around a
+/// literal, and those have its type.
+#[derive(Debug, Clone, Default)]
+pub struct Types {
+    types: HashMap<(usize, usize), Type>,
+}
+
+impl Types {
+    /// The type inferred for `token`, if it was checked
+    pub fn get(&self, token: &Token) -> Option<Type> {
+        self.types.get(&Self::key(token)?).copied()
+    }
+
+    fn insert(&mut self, token: &Token, ty: Type) {
+        if let Some(key) = Self::key(token) {
+            self.types.insert(key, ty);
+        }
+    }
+
+    fn key(token: &Token) -> Option<(usize, usize)> {
+        token.span().map(|span| (span.offset, span.length))
+    }
+}
+
+/// The types a function takes and gives back
+#[derive(Debug, Clone, PartialEq)]
+pub struct Signature {
+    pub parameters: Vec<Type>,
+    pub returns: Type,
+}
+
+impl Signature {
+    /// The signature of a function definition. Parameters and return values
+    /// without an annotation are `i32`s.
+    pub fn of(parameters: &[(String, Option<Type>)], return_type: Option<Type>) -> Self {
+        Signature {
+            parameters: parameters
+                .iter()
+                .map(|(_, annotation)| annotation.unwrap_or(Type::Int))
+                .collect(),
+            returns: return_type.unwrap_or(Type::Int),
+        }
+    }
+}
+
+/// Infers the types of a tree and reports the places where they do not fit.
+/// The bindings and functions of the top level outlive a `check`, so a
+/// long-lived `TypeChecker` can check one chunk of a program at a time, like
+/// the `Compiler` compiles them.
+///
+/// Where an annotation says what type a value should have, that type is a
+/// hint for the value: an integer literal that is hinted to be an `f64` is
+/// taken for a float, so `let x: f64 = 1` binds `1.0`.
+#[derive(Debug, Clone)]
+pub struct TypeChecker {
+    /// The type of every binding in scope, innermost scope last
+    scopes: Vec<HashMap<String, Type>>,
+    /// The signature of every function defined so far, by name
+    functions: HashMap<String, Signature>,
+    /// The return type of the function being checked
+    return_type: Type,
+    /// The types inferred so far
+    types: Types,
+    /// Errors found so far, each with the span of the node it is about
+    errors: Vec<(Span, TypeError)>,
+}
+
+impl Default for TypeChecker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TypeChecker {
+    pub fn new() -> Self {
+        TypeChecker {
+            scopes: vec![HashMap::new()],
+            functions: HashMap::new(),
+            return_type: Type::Int,
+            types: Types::default(),
+            errors: vec![],
+        }
+    }
+
+    /// Checks `program`, giving back the type of each of its nodes, or the
+    /// errors found with the span of the node each one is about
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lrvmism::program_parsers::program_parser;
+    /// use lrvmism::type_checker::TypeChecker;
+    /// let (_, program) = program_parser("1 + 2.5".into()).unwrap();
+    /// let errors = TypeChecker::new().check(&program).unwrap_err();
+    /// // The error is about all of `1 + 2.5`
+    /// assert_eq!((0, 7), (errors[0].0.offset, errors[0].0.length));
+    /// ```
+    pub fn check(&mut self, program: &Token) -> Result<Types, Vec<(Span, TypeError)>> {
+        self.types = Types::default();
+        self.errors.clear();
+        self.check_token(program);
+        if self.errors.is_empty() {
+            Ok(std::mem::take(&mut self.types))
+        } else {
+            Err(std::mem::take(&mut self.errors))
+        }
+    }
+
+    /// Infers the type of `token`. `None` stands for a type that cannot be
+    /// known, like that of an undefined variable, which the `Compiler`
+    /// reports; it fits anywhere.
+    fn check_token(&mut self, token: &Token) -> Option<Type> {
+        self.check_hinted(token, None)
+    }
+
+    /// Infers the type of `token` like `check_token`, with `hint` being the
+    /// type an annotation expects it to have
+    fn check_hinted(&mut self, token: &Token, hint: Option<Type>) -> Option<Type> {
+        // Only floats change how a literal is read
+        let hint = hint.filter(|hint| *hint == Type::Float);
+        let ty = match token {
+            Token::Integer { .. } => Some(hint.unwrap_or(Type::Int)),
+            Token::Float { .. } => Some(Type::Float),
+            Token::String { .. } => Some(Type::String),
+            Token::Identifier { name, .. } => self.lookup(name),
+            Token::Factor { value, .. } => self.check_hinted(value, hint),
+            Token::Term { left, right, .. } | Token::Expression { left, right, .. } => {
+                let mut ty = self.check_hinted(left, hint);
+                for (operator, operand) in right {
+                    let operand_ty = self.check_hinted(operand, hint);
+                    // The operation so far, from `left` to this operand
+                    let span = left.span_to(operand);
+                    ty = self.arithmetic(operator, ty, operand_ty, span);
+                }
+                ty
+            }
+            Token::Comparison {
+                left,
+                operator,
+                right,
+                ..
+            } => {
+                let left_ty = self.check_token(left);
+                let right_ty = self.check_token(right);
+                self.comparison(operator, left_ty, right_ty, span_of(token))
+            }
+            Token::Logical {
+                left,
+                operator,
+                right,
+                ..
+            } => {
+                let left_ty = self.check_token(left);
+                self.logical_operand(operator, left_ty, span_of(token));
+                let right_ty = self.check_token(right);
+                self.logical_operand(operator, right_ty, span_of(token));
+                Some(Type::Bool)
+            }
+            Token::Not { value, .. } => {
+                let ty = self.check_token(value);
+                self.logical_operand(token, ty, span_of(token));
+                Some(Type::Bool)
+            }
+            Token::Boolean { .. } => Some(Type::Bool),
+            Token::Negation { value, .. } | Token::Identity { value, .. } => {
+                let ty = self.check_hinted(value, hint)?;
+                if ty.as_number().is_none() {
+                    let operator = match token {
+                        Token::Negation { .. } => "-",
+                        _ => "+",
+                    };
+                    self.error(
+                        span_of(token),
+                        TypeError::UnsupportedOperand {
+                            operator: operator.to_string(),
+                            found: ty,
+                        },
+                    );
+                }
+                ty.as_number()
+            }
+            Token::Power { base, exponent, .. } => {
+                let base_ty = self.check_hinted(base, hint);
+                let exponent_ty = self.check_token(exponent);
+                self.power(base_ty, exponent_ty, span_of(token))
+            }
+            Token::Bitwise {
+                left,
+                operator,
+                right,
+                ..
+            } => {
+                let left_ty = self.check_token(left);
+                self.integer_operand(operator, left_ty, span_of(token));
+                let right_ty = self.check_token(right);
+                self.integer_operand(operator, right_ty, span_of(token));
+                Some(Type::Int)
+            }
+            Token::Program { statements, .. } => {
+                // Functions can be called before they are defined
+                for statement in statements {
+                    self.declare_function(statement);
+                }
+                for statement in statements {
+                    self.check_token(statement);
+                }
+                // A program has no value, and gets no entry in `Types`: a
+                // program of a single statement has that statement's span
+                None
+            }
+            Token::Block { statements, .. } => {
+                self.scopes.push(HashMap::new());
+                let ty = self.statements(statements, hint);
+                self.scopes.pop();
+                ty
+            }
+            Token::Let {
+                name,
+                annotation,
+                value,
+                ..
+            } => {
+                let ty = match self.check_hinted(value, *annotation) {
+                    Some(found) => match annotation {
+                        Some(expected) => {
+                            if !expected.accepts(found) {
+                                self.error(
+                                    span_of(value),
+                                    TypeError::MismatchedAnnotation {
+                                        expected: *expected,
+                                        found,
+                                    },
+                                );
+                            }
+                            Some(*expected)
+                        }
+                        // A value-less `let` binds 0
+                        None if found == Type::Unit => Some(Type::Int),
+                        None => Some(found),
+                    },
+                    None => *annotation,
+                };
+                if let Some(ty) = ty {
+                    if let Some(scope) = self.scopes.last_mut() {
+                        scope.insert(name.clone(), ty);
+                    }
+                }
+                Some(Type::Unit)
+            }
+            Token::Assignment {
+                target,
+                operator,
+                value,
+                ..
+            } => {
+                let target_ty = target.as_identifier().and_then(|name| self.lookup(name));
+                let value_ty = self.check_hinted(value, target_ty);
+                match operator.as_ref() {
+                    Token::AssignOperator => {
+                        if let (Some(target_ty), Some(value_ty)) = (target_ty, value_ty) {
+                            if target_ty != value_ty
+                                && !(target_ty.is_integral() && value_ty.is_integral())
+                            {
+                                self.error(
+                                    span_of(token),
+                                    TypeError::MismatchedOperands {
+                                        operator: operator.to_string(),
+                                        left: target_ty,
+                                        right: value_ty,
+                                    },
+                                );
+                            }
+                        }
+                    }
+                    operator => {
+                        self.arithmetic(operator, target_ty, value_ty, span_of(token));
+                    }
+                }
+                Some(Type::Unit)
+            }
+            Token::If {
+                condition,
+                body,
+                alternative,
+                ..
+            } => {
+                self.condition(condition);
+                let body_ty = self.check_hinted(body, hint);
+                match alternative {
+                    Some(alternative) => {
+                        let alternative_ty = self.check_hinted(alternative, hint);
+                        self.branches(body_ty, alternative_ty, span_of(alternative))
+                    }
+                    None => body_ty,
+                }
+            }
+            Token::While {
+                condition, body, ..
+            } => {
+                self.condition(condition);
+                self.check_token(body);
+                Some(Type::Unit)
+            }
+            Token::Function {
+                name,
+                parameters,
+                return_type,
+                body,
+                ..
+            } => {
+                if !self.functions.contains_key(name) {
+                    self.declare_function(token);
+                }
+                let signature = Signature::of(parameters, *return_type);
+                // A function only sees its parameters, like in the `Compiler`
+                let parameters = parameters
+                    .iter()
+                    .map(|(parameter, _)| parameter.clone())
+                    .zip(signature.parameters)
+                    .collect();
+                let scopes = std::mem::replace(&mut self.scopes, vec![parameters]);
+                let outer_return_type = std::mem::replace(&mut self.return_type, signature.returns);
+                let ty = self.check_hinted(body, Some(signature.returns));
+                // A function without a return type whose body has no value
+                // gives 0
+                let ty = ty.filter(|ty| return_type.is_some() || *ty != Type::Unit);
+                self.returned(ty, span_of(token));
+                self.scopes = scopes;
+                self.return_type = outer_return_type;
+                Some(Type::Unit)
+            }
+            Token::Return { value, .. } => {
+                if let Some(value) = value {
+                    let ty = self.check_hinted(value, Some(self.return_type));
+                    self.returned(ty, span_of(token));
+                }
+                Some(Type::Unit)
+            }
+            Token::Call {
+                name, arguments, ..
+            } => {
+                let signature = self.functions.get(name).cloned();
+                for (i, argument) in arguments.iter().enumerate() {
+                    let expected = signature
+                        .as_ref()
+                        .and_then(|signature| signature.parameters.get(i).copied());
+                    let ty = self.check_hinted(argument, expected);
+                    if let (Some(expected), Some(found)) = (expected, ty) {
+                        if !expected.accepts(found) {
+                            self.error(
+                                span_of(argument),
+                                TypeError::InvalidArgument {
+                                    name: name.clone(),
+                                    expected,
+                                    found,
+                                },
+                            );
+                        }
+                    }
+                }
+                // Builtins have no value of their own and give 0
+                Some(signature.map_or(Type::Int, |signature| signature.returns))
+            }
+            Token::Break { .. } | Token::Continue { .. } => Some(Type::Unit),
+            // Operators are checked as part of the node they belong to
+            _ => None,
+        };
+        if let Some(ty) = ty {
+            self.types.insert(token, ty);
+        }
+        ty
+    }
+
+    /// Checks the statements of a block. The block has the type of its last
+    /// statement, which is the one `hint` is for.
+    fn statements(&mut self, statements: &[Token], hint: Option<Type>) -> Option<Type> {
+        let mut ty = Some(Type::Unit);
+        for (i, statement) in statements.iter().enumerate() {
+            let hint = hint.filter(|_| i + 1 == statements.len());
+            ty = self.check_hinted(statement, hint);
+        }
+        ty
+    }
+
+    /// Records the signature of `token` if it is a function definition
+    fn declare_function(&mut self, token: &Token) {
+        if let Token::Function {
+            name,
+            parameters,
+            return_type,
+            ..
+        } = token
+        {
+            if !BUILTINS.contains(&name.as_str()) {
+                self.functions
+                    .insert(name.clone(), Signature::of(parameters, *return_type));
+            }
+        }
+    }
+
+    /// The type of the binding `name`, from the innermost scope that has it
+    fn lookup(&self, name: &str) -> Option<Type> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name).copied())
+    }
+
+    fn error(&mut self, span: Span, error: TypeError) {
+        self.errors.push((span, error));
+    }
+
+    /// The type of `left <operator> right` for an arithmetic `operator`,
+    /// the operation being at `span`. Both sides have to be ints, or both
+    /// floats.
+    fn arithmetic(
+        &mut self,
+        operator: &Token,
+        left: Option<Type>,
+        right: Option<Type>,
+        span: Span,
+    ) -> Option<Type> {
+        let (left, right) = match (left, right) {
+            (Some(left), Some(right)) => (left, right),
+            (Some(ty), None) | (None, Some(ty)) => return Some(ty),
+            (None, None) => return None,
+        };
+        let operator = arithmetic_symbol(operator);
+        let (left, right) = match (left.as_number(), right.as_number()) {
+            (Some(left), Some(right)) => (left, right),
+            (None, _) | (_, None) => {
+                let found = if left.as_number().is_none() {
+                    left
+                } else {
+                    right
+                };
+                self.error(
+                    span,
+                    TypeError::UnsupportedOperand {
+                        operator: operator.to_string(),
+                        found,
+                    },
+                );
+                return None;
+            }
+        };
+        if left != right {
+            self.error(
+                span,
+                TypeError::MismatchedOperands {
+                    operator: operator.to_string(),
+                    left,
+                    right,
+                },
+            );
+            return None;
+        }
+        // lrvm has no float-to-integer conversion to truncate a quotient with
+        if operator == "%" && left == Type::Float {
+            self.error(
+                span,
+                TypeError::UnsupportedOperand {
+                    operator: operator.to_string(),
+                    found: left,
+                },
+            );
+            return None;
+        }
+        Some(left)
+    }
+
+    /// The type of `base ** exponent`, which is at `span`: that of the base,
+    /// which is an int or a float, as the exponent has to be an int
+    fn power(&mut self, base: Option<Type>, exponent: Option<Type>, span: Span) -> Option<Type> {
+        if let Some(found) = exponent.filter(|ty| !ty.is_integral()) {
+            self.error(span, TypeError::InvalidExponent { found });
+        }
+        let base = base?;
+        if base.as_number().is_none() {
+            self.error(
+                span,
+                TypeError::UnsupportedOperand {
+                    operator: "**".to_string(),
+                    found: base,
+                },
+            );
+        }
+        base.as_number()
+    }
+
+    /// Checks an operand of one of the integer operators `&`, `|`, `^`, `<<`
+    /// and `>>`, the operation being at `span`
+    fn integer_operand(&mut self, operator: &Token, ty: Option<Type>, span: Span) {
+        if let Some(found) = ty.filter(|ty| !ty.is_integral()) {
+            self.error(
+                span,
+                TypeError::UnsupportedOperand {
+                    operator: operator.to_string(),
+                    found,
+                },
+            );
+        }
+    }
+
+    /// The type of a comparison at `span`: a bool, as long as both
+    /// sides are ints or both floats, bools counting as ints
+    fn comparison(
+        &mut self,
+        operator: &Token,
+        left: Option<Type>,
+        right: Option<Type>,
+        span: Span,
+    ) -> Option<Type> {
+        if let (Some(left), Some(right)) = (left, right) {
+            for ty in [left, right] {
+                if ty.as_number().is_none() {
+                    self.error(
+                        span,
+                        TypeError::UnsupportedOperand {
+                            operator: operator.to_string(),
+                            found: ty,
+                        },
+                    );
+                    return Some(Type::Bool);
+                }
+            }
+            if left.as_number() != right.as_number() {
+                self.error(
+                    span,
+                    TypeError::MismatchedOperands {
+                        operator: operator.to_string(),
+                        left,
+                        right,
+                    },
+                );
+            }
+        }
+        Some(Type::Bool)
+    }
+
+    /// Checks an operand of `&&`, `||` or `!`, the operation being at `span`.
+    /// Like a condition, it has to be a bool or an int.
+    fn logical_operand(&mut self, operator: &Token, ty: Option<Type>, span: Span) {
+        if let Some(found) = ty.filter(|ty| !ty.is_integral()) {
+            let operator = match operator {
+                Token::Not { .. } => "!".to_string(),
+                operator => operator.to_string(),
+            };
+            self.error(span, TypeError::UnsupportedOperand { operator, found });
+        }
+    }
+
+    /// Checks the condition of an `if` or `while`
+    fn condition(&mut self, condition: &Token) {
+        if let Some(ty) = self.check_token(condition) {
+            if !ty.is_integral() {
+                self.error(
+                    span_of(condition),
+                    TypeError::InvalidCondition { found: ty },
+                );
+            }
+        }
+    }
+
+    /// The type of an `if` whose branches have the types `body` and
+    /// `alternative`, the `else` branch being at `span`. A branch without
+    /// a value gives 0 of whatever type the other branch has.
+    fn branches(
+        &mut self,
+        body: Option<Type>,
+        alternative: Option<Type>,
+        span: Span,
+    ) -> Option<Type> {
+        match (body, alternative) {
+            (Some(Type::Unit), ty) | (ty, Some(Type::Unit)) | (ty, None) | (None, ty) => ty,
+            (Some(body), Some(alternative)) if body == alternative => Some(body),
+            (Some(body), Some(alternative)) if body.is_integral() && alternative.is_integral() => {
+                Some(Type::Int)
+            }
+            (Some(body), Some(alternative)) => {
+                self.error(span, TypeError::MismatchedBranches { body, alternative });
+                None
+            }
+        }
+    }
+
+    /// Checks a value given back by the function being checked, from the
+    /// `return` or function at `span`, against its return type
+    fn returned(&mut self, ty: Option<Type>, span: Span) {
+        if let Some(found) = ty.filter(|found| !self.return_type.accepts(*found)) {
+            self.error(
+                span,
+                TypeError::InvalidReturn {
+                    expected: self.return_type,
+                    found,
+                },
+            );
+        }
+    }
+}
+
+/// Where `token` is, operators having no place of their own
+fn span_of(token: &Token) -> Span {
+    token.span().unwrap_or_default()
+}
+
+/// The arithmetic operator an operator token stands for, with compound
+/// assignments standing for the operation they apply
+fn arithmetic_symbol(operator: &Token) -> &'static str {
+    match operator {
+        Token::AdditionOperator | Token::AddAssignOperator => "+",
+        Token::SubtractionOperator | Token::SubtractAssignOperator => "-",
+        Token::MultiplicationOperator | Token::MultiplyAssignOperator => "*",
+        Token::DivisionOperator | Token::DivideAssignOperator => "/",
+        Token::ModuloOperator => "%",
+        _ => "?",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{program_parsers::program_parser, span::Span, token::Token};
+
+    use super::{Type, TypeChecker, TypeError};
+
+    fn check(source: &str) -> Result<(), Vec<(Span, TypeError)>> {
+        let (_, program) = program_parser(source.into()).unwrap();
+        TypeChecker::new().check(&program).map(|_| ())
+    }
+
+    #[test]
+    fn test_inferred_types() {
+        for (source, expected) in [
+            ("1 + 2 * 3", Type::Int),
+            ("1.5 / 2.0", Type::Float),
+            ("1 < 2", Type::Bool),
+            ("true", Type::Bool),
+            ("7 % 3 + 2 ** 3", Type::Int),
+            ("1.5 ** 2", Type::Float),
+            ("-(1 < 2)", Type::Int),
+            ("let x: f64 = -1\nx * +2.0", Type::Float),
+            ("let x: f64 = 2 ** 3\nx", Type::Float),
+            ("1 << 2 | (1 < 2)", Type::Int),
+            ("1 < 2 && !false", Type::Bool),
+            ("let b: bool = 1 > 2 || true\nb", Type::Bool),
+            ("(1 < 2) + 1", Type::Int),
+            ("\"hi\"", Type::String),
+            ("let x = 1.5\nx * x", Type::Float),
+            ("if 1 { 2.5 } else { 0.5 }", Type::Float),
+            ("if 1 { 2.5 }", Type::Float),
+            ("fn f(a) { a }\nf(1)", Type::Int),
+            ("fn f(a: f64) -> f64 { a }\nf(1.5)", Type::Float),
+            ("let x: f64 = 1\nx", Type::Float),
+            ("let x = 1", Type::Unit),
+            ("fn f() { let y = 1 }\nf()", Type::Int),
+        ] {
+            let (_, program) = program_parser(source.into()).unwrap();
+            let types = TypeChecker::new().check(&program).unwrap();
+            match &program {
+                Token::Program { statements, .. } => assert_eq!(
+                    Some(expected),
+                    types.get(statements.last().unwrap()),
+                    "{}",
+                    source
+                ),
+                other => panic!("expected a program, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_type_errors() {
+        for (source, location, error) in [
+            (
+                "1 + 2.5",
+                (1, 1),
+                TypeError::MismatchedOperands {
+                    operator: "+".to_string(),
+                    left: Type::Int,
+                    right: Type::Float,
+                },
+            ),
+            (
+                "let x = 1.5\nx * 2 - 1",
+                (2, 1),
+                TypeError::MismatchedOperands {
+                    operator: "*".to_string(),
+                    left: Type::Float,
+                    right: Type::Int,
+                },
+            ),
+            (
+                "1.0 < 2",
+                (1, 1),
+                TypeError::MismatchedOperands {
+                    operator: "<".to_string(),
+                    left: Type::Float,
+                    right: Type::Int,
+                },
+            ),
+            (
+                "(1 < 2) + 1.5",
+                (1, 1),
+                TypeError::MismatchedOperands {
+                    operator: "+".to_string(),
+                    left: Type::Int,
+                    right: Type::Float,
+                },
+            ),
+            (
+                "\"a\" * 2",
+                (1, 1),
+                TypeError::UnsupportedOperand {
+                    operator: "*".to_string(),
+                    found: Type::String,
+                },
+            ),
+            (
+                "1 < 2 && 0.5",
+                (1, 1),
+                TypeError::UnsupportedOperand {
+                    operator: "&&".to_string(),
+                    found: Type::Float,
+                },
+            ),
+            (
+                "7.5 % 2.0",
+                (1, 1),
+                TypeError::UnsupportedOperand {
+                    operator: "%".to_string(),
+                    found: Type::Float,
+                },
+            ),
+            (
+                "2 ** 0.5",
+                (1, 1),
+                TypeError::InvalidExponent { found: Type::Float },
+            ),
+            (
+                "0.5 + 1.5 & 3",
+                (1, 1),
+                TypeError::UnsupportedOperand {
+                    operator: "&".to_string(),
+                    found: Type::Float,
+                },
+            ),
+            (
+                "2 * -\"a\"",
+                (1, 5),
+                TypeError::UnsupportedOperand {
+                    operator: "-".to_string(),
+                    found: Type::String,
+                },
+            ),
+            (
+                "1 + !\"a\"",
+                (1, 5),
+                TypeError::UnsupportedOperand {
+                    operator: "!".to_string(),
+                    found: Type::String,
+                },
+            ),
+            (
+                "let x = 1\nx = 0.5",
+                (2, 1),
+                TypeError::MismatchedOperands {
+                    operator: "=".to_string(),
+                    left: Type::Int,
+                    right: Type::Float,
+                },
+            ),
+            (
+                "if 1 { 1 } else { 1.0 }",
+                (1, 17),
+                TypeError::MismatchedBranches {
+                    body: Type::Int,
+                    alternative: Type::Float,
+                },
+            ),
+            (
+                "while 0.5 { }",
+                (1, 7),
+                TypeError::InvalidCondition { found: Type::Float },
+            ),
+            (
+                "fn f(a, b) { a }\nf(1, 2.5)",
+                (2, 6),
+                TypeError::InvalidArgument {
+                    name: "f".to_string(),
+                    expected: Type::Int,
+                    found: Type::Float,
+                },
+            ),
+            (
+                "fn f() { 2.5 }",
+                (1, 1),
+                TypeError::InvalidReturn {
+                    expected: Type::Int,
+                    found: Type::Float,
+                },
+            ),
+            (
+                "fn f(a: f64) -> f64 {\n  if a > 0.0 { return a > 1.0 }\n  a\n}",
+                (2, 16),
+                TypeError::InvalidReturn {
+                    expected: Type::Float,
+                    found: Type::Bool,
+                },
+            ),
+            (
+                "let x: i32 = 2.5",
+                (1, 14),
+                TypeError::MismatchedAnnotation {
+                    expected: Type::Int,
+                    found: Type::Float,
+                },
+            ),
+            (
+                "fn f() -> f64 { while 0 {} }",
+                (1, 1),
+                TypeError::InvalidReturn {
+                    expected: Type::Float,
+                    found: Type::Unit,
+                },
+            ),
+            (
+                "let c = 1\nlet b: bool = if c { let y = 1 }",
+                (2, 15),
+                TypeError::MismatchedAnnotation {
+                    expected: Type::Bool,
+                    found: Type::Unit,
+                },
+            ),
+            (
+                "fn f(a: f64) -> f64 { a }\nf(if 1 { let x = 1 })",
+                (2, 3),
+                TypeError::InvalidArgument {
+                    name: "f".to_string(),
+                    expected: Type::Float,
+                    found: Type::Unit,
+                },
+            ),
+            (
+                "let x: f64 = 1\nx + 1",
+                (2, 1),
+                TypeError::MismatchedOperands {
+                    operator: "+".to_string(),
+                    left: Type::Float,
+                    right: Type::Int,
+                },
+            ),
+            (
+                "let x = 1\ng(x)\nfn g(a: f64) { 1 }",
+                (2, 3),
+                TypeError::InvalidArgument {
+                    name: "g".to_string(),
+                    expected: Type::Float,
+                    found: Type::Int,
+                },
+            ),
+        ] {
+            let errors: Vec<((usize, usize), TypeError)> = check(source)
+                .unwrap_err()
+                .into_iter()
+                .map(|(span, error)| ((span.line, span.column), error))
+                .collect();
+            assert_eq!(vec![(location, error)], errors, "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_annotations_decide_literals() {
+        let (_, program) =
+            program_parser("let x: f64 = 2 * 3\nfn f(a: f64) { 0 }\nf(4)".into()).unwrap();
+        let types = TypeChecker::new().check(&program).unwrap();
+        let literals: Vec<Option<Type>> = integer_literals(&program)
+            .into_iter()
+            .map(|literal| types.get(literal))
+            .collect();
+        assert_eq!(
+            vec![
+                Some(Type::Float),
+                Some(Type::Float),
+                Some(Type::Int),
+                Some(Type::Float)
+            ],
+            literals
+        );
+
+        // The types belong to the source, not to where the tree lives
+        let moved = Box::new(program.clone());
+        let literals: Vec<Option<Type>> = integer_literals(&moved)
+            .into_iter()
+            .map(|literal| types.get(literal))
+            .collect();
+        assert_eq!(Some(Type::Int), literals[2]);
+        assert_eq!(Some(Type::Float), literals[3]);
+    }
+
+    /// Every integer literal of `token`, in source order
+    fn integer_literals(token: &Token) -> Vec<&Token> {
+        match token {
+            Token::Integer { .. } => vec![token],
+            other => other
+                .children()
+                .into_iter()
+                .flat_map(integer_literals)
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_bindings_are_scoped() {
+        assert!(check("let x = 1\nif 1 { let x = 2.5\nx * 2.0 }\nx + 1").is_ok());
+        assert!(check("print(\"a\")\nprint(1.5)\nprint(1 < 2)").is_ok());
+        // Undefined names are left for the `Compiler` to report
+        assert!(check("y + 1.5").is_ok());
+
+        let mut checker = TypeChecker::new();
+        let (_, first) = program_parser("let x = 1.5".into()).unwrap();
+        let (_, second) = program_parser("x + 1".into()).unwrap();
+        assert!(checker.check(&first).is_ok());
+        assert!(checker.check(&second).is_err());
+    }
+}
@@ -1,7 +1,12 @@
-use std::u8;
+use std::{collections::HashMap, fmt, ops::Range, u8};
 
-use crate::token::Token;
-use lrvm::assembler::{prepend_header, Assembler};
+use crate::{
+    error::CompileError,
+    runtime::Routine,
+    token::Token,
+    type_checker::{Signature, Type, Types},
+};
+use lrvm::assembler::Assembler;
 
 pub trait Visitor {
     fn visit_token(&mut self, node: &Token);
@@ -13,60 +18,1342 @@ pub trait Visitor {
 pub struct Compiler {
     /// Unused Registers
     free_registers: Vec<u8>,
+    /// Unused float registers. Floats live in a register file of their own.
+    free_float_registers: Vec<u8>,
     /// Used Registers
-    used_registers: Vec<u8>,
+    used_registers: Vec<Register>,
     /// The assembly statements created so far. These are just Strings that are
     /// emitted by the `Compiler` as it walks the tree
     assembly: Vec<String>,
     /// An `Assembler` for the lrvm VM, so the `Compiler` can emit bytecode directly
     assembler: Assembler,
+    /// The register holding the value of the last expression of the program
+    result_register: Option<Register>,
+    /// Where the value of every `let` binding lives
+    bindings: HashMap<String, Location>,
+    /// Bytes of VM heap needed for spilled bindings
+    spill_bytes: usize,
+    /// Bytes of VM heap that programs assembled so far have already allocated
+    allocated_bytes: usize,
+    /// How many labels have been generated, so every label is unique
+    label_count: usize,
+    /// The names bound in each block that is currently open, innermost last.
+    /// These bindings go away when their block ends.
+    scopes: Vec<Vec<String>>,
+    /// The head and exit labels of each loop that is currently open,
+    /// innermost last, for `continue` and `break` to jump to
+    loops: Vec<(String, String)>,
+    /// Every function defined so far, by name
+    functions: HashMap<String, Function>,
+    /// The code of every function defined so far. It is placed after the
+    /// `HLT` of each program, so a long-lived `Compiler` can keep calling
+    /// functions defined in earlier chunks.
+    function_assembly: Vec<String>,
+    /// Whether the code being generated is the body of a function
+    in_function: bool,
+    /// The type the function being generated gives back
+    return_type: Type,
+    /// The lowest float register the function being generated has used
+    float_floor: u8,
+    /// The functions the function being generated calls
+    callees: Vec<String>,
+    /// The calls made outside of functions while float registers were in
+    /// use, each with the highest float register in use. They are checked
+    /// once every function of the program has been generated.
+    float_calls: Vec<(String, u8)>,
+    /// The label of every string literal placed in the `.data` section so
+    /// far, by text, so that each text is only stored once
+    strings: HashMap<String, String>,
+    /// The `.data` section: a labelled `.asciiz` line per string literal
+    data: Vec<String>,
+    /// The runtime routines linked into `function_assembly` so far
+    routines: Vec<Routine>,
+    /// The types the `TypeChecker` inferred for the tree being compiled
+    types: Types,
+    /// Errors found while walking the tree. The walk carries on after an error
+    /// so that `compile` can report it instead of the `Compiler` panicking.
+    errors: Vec<CompileError>,
+}
+
+/// Register kept out of the allocation pool, so the `Compiler` always has
+/// somewhere to build intermediate values. The float register with the same
+/// number is kept out of the float pool for the same reason.
+pub const SCRATCH_REGISTER: u8 = 31;
+
+/// Registers that pass the arguments of a call, first argument first. They
+/// are kept out of the allocation pool.
+pub const ARGUMENT_REGISTERS: Range<u8> = 24..30;
+
+/// Register a function leaves its return value in
+pub const RETURN_REGISTER: u8 = 30;
+
+/// Functions provided by the compiler itself. Their names cannot be used for
+/// functions of a program.
+pub const BUILTINS: &[&str] = &["print", "println"];
+
+/// Once a `let` would leave fewer free registers than this, its value is
+/// spilled to the heap instead, keeping registers around for temporaries
+pub const MIN_FREE_REGISTERS: usize = 8;
+
+/// Size in bytes of a spill slot on the VM heap
+const SPILL_SLOT_SIZE: usize = 4;
+
+/// A register in one of the VM's two register files. It displays as its
+/// number, which is how instructions name it either way.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Register {
+    Int(u8),
+    Float(u8),
+}
+
+impl Register {
+    pub fn number(&self) -> u8 {
+        match self {
+            Register::Int(number) | Register::Float(number) => *number,
+        }
+    }
+
+    pub fn is_float(&self) -> bool {
+        matches!(self, Register::Float(_))
+    }
+}
+
+impl fmt::Display for Register {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.number())
+    }
+}
+
+/// Where the value of a binding is kept
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Location {
+    /// The value stays in a register for as long as the binding lives
+    Register(Register),
+    /// The value lives on the VM heap at this byte offset. Only integers
+    /// are spilled, as lrvm has no instructions to move floats to memory.
+    Spill(usize),
+}
+
+/// What a call needs to know about a function
+#[derive(Debug, Clone)]
+struct Function {
+    /// The label the function's code starts at
+    label: String,
+    /// The types of the parameters and of the value the function gives back
+    signature: Signature,
+    /// Whether the function's code has been generated yet. Functions are
+    /// declared before the program is walked so they can be called before
+    /// their definition.
+    defined: bool,
+    /// The lowest float register the function's own code uses
+    float_floor: u8,
+    /// The functions the function calls
+    callees: Vec<String>,
+}
+
+/// The register allocation and binding state of a `Compiler`, so that a
+/// failed chunk of a program can be rolled back
+#[derive(Debug, Clone)]
+pub struct Checkpoint {
+    free_registers: Vec<u8>,
+    free_float_registers: Vec<u8>,
+    used_registers: Vec<Register>,
+    bindings: HashMap<String, Location>,
+    spill_bytes: usize,
+    functions: HashMap<String, Function>,
+    function_assembly: Vec<String>,
+    strings: HashMap<String, String>,
+    data: Vec<String>,
+    routines: Vec<Routine>,
 }
 
 impl Compiler {
     pub fn new() -> Self {
-        let mut free_registers: Vec<u8> = (0..31).collect();
-        free_registers.reverse();
-
         Compiler {
-            free_registers,
+            free_registers: register_pool(),
+            free_float_registers: register_pool(),
             used_registers: vec![],
             assembly: vec![],
             assembler: Assembler::new(),
+            result_register: None,
+            bindings: HashMap::new(),
+            spill_bytes: 0,
+            allocated_bytes: 0,
+            label_count: 0,
+            scopes: vec![],
+            loops: vec![],
+            functions: HashMap::new(),
+            function_assembly: vec![],
+            in_function: false,
+            return_type: Type::Int,
+            float_floor: ARGUMENT_REGISTERS.start,
+            callees: vec![],
+            float_calls: vec![],
+            strings: HashMap::new(),
+            data: vec![],
+            routines: vec![],
+            types: Types::default(),
+            errors: vec![],
         }
     }
 
+    /// Hands over the types inferred for the tree that is compiled next. They
+    /// decide the register file the value of an `if` goes into.
+    pub fn set_types(&mut self, types: Types) {
+        self.types = types;
+    }
+
+    /// Forgets the assembly and errors emitted so far while keeping the
+    /// register bookkeeping and bindings, so a long-lived `Compiler` can
+    /// compile one chunk of a program at a time
+    pub fn clear_assembly(&mut self) {
+        self.assembly.clear();
+        self.result_register = None;
+        self.errors.clear();
+    }
+
+    /// Saves the register allocation and bindings
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            free_registers: self.free_registers.clone(),
+            free_float_registers: self.free_float_registers.clone(),
+            used_registers: self.used_registers.clone(),
+            bindings: self.bindings.clone(),
+            spill_bytes: self.spill_bytes,
+            functions: self.functions.clone(),
+            function_assembly: self.function_assembly.clone(),
+            strings: self.strings.clone(),
+            data: self.data.clone(),
+            routines: self.routines.clone(),
+        }
+    }
+
+    /// Goes back to the register allocation and bindings of `checkpoint`
+    pub fn restore(&mut self, checkpoint: Checkpoint) {
+        self.free_registers = checkpoint.free_registers;
+        self.free_float_registers = checkpoint.free_float_registers;
+        self.used_registers = checkpoint.used_registers;
+        self.bindings = checkpoint.bindings;
+        self.spill_bytes = checkpoint.spill_bytes;
+        self.functions = checkpoint.functions;
+        self.function_assembly = checkpoint.function_assembly;
+        self.strings = checkpoint.strings;
+        self.data = checkpoint.data;
+        self.routines = checkpoint.routines;
+    }
+
+    /// Where the binding called `name` lives, if there is one
+    pub fn binding(&self, name: &str) -> Option<Location> {
+        self.bindings.get(name).copied()
+    }
+
+    /// The errors found while walking the tree so far
+    pub fn errors(&self) -> &[CompileError] {
+        &self.errors
+    }
+
+    /// The register that holds the value of the program's final expression
+    /// once it has run, if the program had any expression at all
+    pub fn result_register(&self) -> Option<Register> {
+        self.result_register
+    }
+
     pub fn prepend_two_section(&self, input: &str) -> String {
         let mut instructions = String::new();
         let data_section = input.contains(".data");
         let code_section = input.contains(".code");
-        if !code_section {
-            instructions.push_str(".code\n");
-        }
         if !data_section {
             instructions.push_str(".data\n");
         }
+        if !code_section {
+            instructions.push_str(".code\n");
+        }
         instructions.push_str(input);
         instructions
     }
 
+    /// Returns the program exactly as it is handed to the `Assembler`: the
+    /// emitted assembly lines with the `.data` and `.code` sections in place
+    pub fn assembly(&self) -> String {
+        let mut lines = vec![];
+        if !self.data.is_empty() {
+            lines.push(".data".to_string());
+            lines.extend(self.data.iter().cloned());
+            lines.push(".code".to_string());
+        }
+        // Grow the heap up front for any spill slots this program added
+        if self.spill_bytes > self.allocated_bytes {
+            let bytes = (self.spill_bytes - self.allocated_bytes) as i64;
+            lines.extend(load_integer_lines(SCRATCH_REGISTER, bytes));
+            lines.push(format!("ALOC ${}", SCRATCH_REGISTER));
+        }
+        lines.extend(self.assembly.iter().cloned());
+        lines.extend(self.function_assembly.iter().cloned());
+        let program = lines.join("\n");
+        self.prepend_two_section(&program)
+    }
+
     /// Takes a Vector of Strings that represent the text of a program and compiles
     /// it into bytecode
-    pub fn compile(&mut self) -> Vec<u8> {
-        let program = self.assembly.join("\n");
-        let program = self.prepend_two_section(&program);
-        let bytecode = self.assembler.assemble(&program);
-        match bytecode {
-            Ok(b) => b,
-            Err(e) => {
-                eprintln!("Error on compile: {:#?}", e);
-                vec![]
+    pub fn compile(&mut self) -> Result<Vec<u8>, CompileError> {
+        if let Some(error) = self.errors.first() {
+            return Err(error.clone());
+        }
+        let program = self.assembly();
+        let bytecode = self
+            .assembler
+            .assemble(&program)
+            .map_err(|e| CompileError::Assemble {
+                message: format!("{:?}", e),
+            })?;
+        self.allocated_bytes = self.spill_bytes;
+        Ok(bytecode)
+    }
+
+    /// Generates a label that has not been used yet. The assembler only
+    /// accepts alphanumeric label names, so the counter is appended directly.
+    fn new_label(&mut self, prefix: &str) -> String {
+        let label = format!("{}{}", prefix, self.label_count);
+        self.label_count += 1;
+        label
+    }
+
+    /// Takes a register from the pool. Running out is reported as an error
+    /// and the scratch register handed out instead, so the walk can go on.
+    fn allocate_register(&mut self) -> Register {
+        Register::Int(self.allocate_from(false))
+    }
+
+    /// Takes a register from the float pool, like `allocate_register`
+    fn allocate_float_register(&mut self) -> Register {
+        Register::Float(self.allocate_from(true))
+    }
+
+    /// Takes a register from the same register file as `register`
+    fn allocate_like(&mut self, register: Register) -> Register {
+        if register.is_float() {
+            self.allocate_float_register()
+        } else {
+            self.allocate_register()
+        }
+    }
+
+    fn allocate_from(&mut self, float: bool) -> u8 {
+        let pool = if float {
+            &mut self.free_float_registers
+        } else {
+            &mut self.free_registers
+        };
+        match pool.pop() {
+            Some(register) => {
+                if float {
+                    self.float_floor = self.float_floor.min(register);
+                }
+                register
+            }
+            None => {
+                if !self.errors.contains(&CompileError::OutOfRegisters) {
+                    self.errors.push(CompileError::OutOfRegisters);
+                }
+                SCRATCH_REGISTER
+            }
+        }
+    }
+
+    /// Hands `register` back to its pool. The scratch registers never join.
+    fn free_register(&mut self, register: Register) {
+        match register {
+            Register::Int(SCRATCH_REGISTER) | Register::Float(SCRATCH_REGISTER) => {}
+            Register::Int(number) => self.free_registers.push(number),
+            Register::Float(number) => self.free_float_registers.push(number),
+        }
+    }
+
+    /// Copies the value of `source` into `destination`, which has to be in
+    /// the same register file
+    fn copy_register(&mut self, source: Register, destination: Register) {
+        let suffix = float_suffix(source);
+        self.assembly
+            .push(format!("LOAD{} ${} #0", suffix, SCRATCH_REGISTER));
+        self.assembly.push(format!(
+            "ADD{} ${} ${} ${}",
+            suffix, source, SCRATCH_REGISTER, destination
+        ));
+    }
+
+    /// Puts 0 in `register`
+    fn load_zero(&mut self, register: Register) {
+        self.assembly
+            .push(format!("LOAD{} ${} #0", float_suffix(register), register));
+    }
+
+    /// Reports an error unless `left` and `right` are in the same register
+    /// file: lrvm has no instruction that mixes integers and floats, nor one
+    /// that converts between them
+    fn check_same_kind(&mut self, left: Register, right: Register, operation: &str) -> bool {
+        if left.is_float() == right.is_float() {
+            return true;
+        }
+        self.errors.push(CompileError::MixedOperands {
+            operation: operation.to_string(),
+        });
+        false
+    }
+
+    /// Binds `name` to the value in `register`, which the binding takes
+    /// ownership of. The value is spilled to the heap when registers run low,
+    /// except in functions: spill slots are shared by every call, so they
+    /// would be overwritten by recursion.
+    fn bind(&mut self, name: &str, register: Register) {
+        if self.bindings.contains_key(name) {
+            self.errors.push(CompileError::DuplicateBinding {
+                name: name.to_string(),
+            });
+            self.free_register(register);
+            return;
+        }
+
+        let location = if register.is_float()
+            || self.in_function
+            || self.free_registers.len() >= MIN_FREE_REGISTERS
+        {
+            Location::Register(register)
+        } else {
+            let offset = self.spill_bytes;
+            self.spill_bytes += SPILL_SLOT_SIZE;
+            self.load_integer(SCRATCH_REGISTER, offset as i64);
+            self.assembly
+                .push(format!("SETM ${} ${}", SCRATCH_REGISTER, register));
+            self.free_register(register);
+            Location::Spill(offset)
+        };
+        self.bindings.insert(name.to_string(), location);
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.push(name.to_string());
+        }
+    }
+
+    /// Compiles the statements of a block. Only the value of the last
+    /// statement is kept, and bindings made in the block end with it.
+    fn block(&mut self, statements: &[Token]) {
+        self.scopes.push(vec![]);
+        let depth = self.used_registers.len();
+        for (i, statement) in statements.iter().enumerate() {
+            self.visit_token(statement);
+            if i + 1 < statements.len() {
+                self.discard_values(depth);
+            }
+        }
+
+        for name in self.scopes.pop().unwrap_or_default() {
+            if let Some(Location::Register(register)) = self.bindings.remove(&name) {
+                self.free_register(register);
+            }
+        }
+    }
+
+    /// Frees the registers of any values left above `depth` on the stack of
+    /// used registers
+    fn discard_values(&mut self, depth: usize) {
+        while self.used_registers.len() > depth {
+            if let Some(register) = self.used_registers.pop() {
+                self.free_register(register);
+            }
+        }
+    }
+
+    /// Compiles the `if` expression `node`. The value of whichever branch
+    /// runs ends up in one result register, a float register if the `if` was
+    /// inferred to be a float; a branch without a value gives 0.
+    fn if_expression(
+        &mut self,
+        node: &Token,
+        condition: &Token,
+        body: &Token,
+        alternative: Option<&Token>,
+    ) {
+        let result_register = match self.types.get(node) {
+            Some(Type::Float) => self.allocate_float_register(),
+            _ => self.allocate_register(),
+        };
+        let else_label = self.new_label("else");
+        let end_label = self.new_label("endif");
+
+        self.branch_if_false(condition, &else_label);
+        self.branch_value(body, result_register);
+        self.jump(&end_label);
+        self.assembly.push(format!("{}: NOP", else_label));
+        match alternative {
+            Some(alternative) => self.branch_value(alternative, result_register),
+            None => self.load_zero(result_register),
+        }
+        self.assembly.push(format!("{}: NOP", end_label));
+        self.used_registers.push(result_register);
+    }
+
+    /// Compiles a `while` loop. The condition is checked at the loop head
+    /// before every iteration; the loop itself has no value.
+    fn while_loop(&mut self, condition: &Token, body: &Token) {
+        let head_label = self.new_label("loop");
+        let exit_label = self.new_label("endloop");
+
+        self.assembly.push(format!("{}: NOP", head_label));
+        self.branch_if_false(condition, &exit_label);
+        self.loops.push((head_label.clone(), exit_label.clone()));
+        let depth = self.used_registers.len();
+        self.visit_token(body);
+        self.discard_values(depth);
+        self.loops.pop();
+        self.jump(&head_label);
+        self.assembly.push(format!("{}: NOP", exit_label));
+    }
+
+    /// Jumps to the head (`continue`) or exit (`break`) of the innermost loop
+    fn loop_control(&mut self, node: &Token) {
+        let (head_label, exit_label) = match self.loops.last() {
+            Some(labels) => labels.clone(),
+            None => {
+                self.errors.push(CompileError::LoopControlOutsideLoop {
+                    keyword: node.to_string(),
+                });
+                return;
+            }
+        };
+        match node {
+            Token::Continue { .. } => self.jump(&head_label),
+            _ => self.jump(&exit_label),
+        }
+    }
+
+    /// Makes the function `name` known so it can be called, possibly before
+    /// its definition has been compiled
+    fn declare_function(
+        &mut self,
+        name: &str,
+        parameters: &[(String, Option<Type>)],
+        return_type: Option<Type>,
+    ) {
+        if BUILTINS.contains(&name) || self.functions.contains_key(name) {
+            self.errors.push(CompileError::DuplicateFunction {
+                name: name.to_string(),
+            });
+            return;
+        }
+        if parameters.len() > ARGUMENT_REGISTERS.len() {
+            self.errors.push(CompileError::TooManyParameters {
+                name: name.to_string(),
+                limit: ARGUMENT_REGISTERS.len(),
+            });
+        }
+        let label = self.new_label("fn");
+        self.functions.insert(
+            name.to_string(),
+            Function {
+                label,
+                signature: Signature::of(parameters, return_type),
+                defined: false,
+                float_floor: ARGUMENT_REGISTERS.start,
+                callees: vec![],
+            },
+        );
+    }
+
+    /// Compiles a function definition into `function_assembly`.
+    ///
+    /// The caller puts the arguments in `ARGUMENT_REGISTERS` and `CALL`s the
+    /// function's label. The function copies them into registers of its
+    /// own, leaves its value in `RETURN_REGISTER` and `RET`urns. Every
+    /// register may be overwritten by the function, so the caller saves the
+    /// ones it still needs on the stack around the call. Float arguments and
+    /// return values use the float registers with the same numbers.
+    ///
+    /// lrvm cannot push float registers, so a function takes its float
+    /// registers from the top of the pool down, while the rest of the
+    /// program takes them from the bottom up. The floats a program keeps
+    /// around a call survive it as long as the two do not meet.
+    fn function(
+        &mut self,
+        name: &str,
+        parameters: &[(String, Option<Type>)],
+        return_type: Option<Type>,
+        body: &Token,
+    ) {
+        match self.functions.get(name) {
+            Some(function) if function.defined => {
+                self.errors.push(CompileError::DuplicateFunction {
+                    name: name.to_string(),
+                });
+                return;
+            }
+            Some(_) => {}
+            None => self.declare_function(name, parameters, return_type),
+        }
+        let Some(function) = self.functions.get_mut(name) else {
+            return;
+        };
+        function.defined = true;
+        let label = function.label.clone();
+        let signature = function.signature.clone();
+
+        // The body starts from scratch: all registers free, nothing bound
+        // but the parameters, and outside of any loop
+        let free_registers = std::mem::replace(&mut self.free_registers, register_pool());
+        let free_float_registers = std::mem::replace(
+            &mut self.free_float_registers,
+            register_pool().into_iter().rev().collect(),
+        );
+        let used_registers = std::mem::take(&mut self.used_registers);
+        let bindings = std::mem::take(&mut self.bindings);
+        let scopes = std::mem::take(&mut self.scopes);
+        let loops = std::mem::take(&mut self.loops);
+        let assembly = std::mem::take(&mut self.assembly);
+        let in_function = std::mem::replace(&mut self.in_function, true);
+        let outer_return_type = std::mem::replace(&mut self.return_type, signature.returns);
+        let outer_float_floor = std::mem::replace(&mut self.float_floor, ARGUMENT_REGISTERS.start);
+        let outer_callees = std::mem::take(&mut self.callees);
+
+        self.assembly.push(format!("{}: NOP", label));
+        for (((parameter, _), ty), argument_register) in parameters
+            .iter()
+            .zip(signature.parameters)
+            .zip(ARGUMENT_REGISTERS)
+        {
+            let argument_register = register_for(ty, argument_register);
+            let register = self.allocate_like(argument_register);
+            self.copy_register(argument_register, register);
+            self.bind(parameter, register);
+        }
+        self.visit_token(body);
+        self.return_value_in_register();
+
+        let body_assembly = std::mem::replace(&mut self.assembly, assembly);
+        self.function_assembly.extend(body_assembly);
+        self.free_registers = free_registers;
+        self.free_float_registers = free_float_registers;
+        self.used_registers = used_registers;
+        self.bindings = bindings;
+        self.scopes = scopes;
+        self.loops = loops;
+        self.in_function = in_function;
+        self.return_type = outer_return_type;
+        let float_floor = std::mem::replace(&mut self.float_floor, outer_float_floor);
+        let callees = std::mem::replace(&mut self.callees, outer_callees);
+        if let Some(function) = self.functions.get_mut(name) {
+            function.float_floor = float_floor;
+            function.callees = callees;
+        }
+    }
+
+    /// Compiles `return`, with the value of `value` or 0
+    fn return_statement(&mut self, value: Option<&Token>) {
+        if !self.in_function {
+            self.errors.push(CompileError::ReturnOutsideFunction);
+            return;
+        }
+        let depth = self.used_registers.len();
+        if let Some(value) = value {
+            self.visit_token(value);
+        }
+        self.return_value_in_register();
+        self.discard_values(depth);
+    }
+
+    /// Moves the value on top of the stack of used registers, or 0 if there
+    /// is none, into `RETURN_REGISTER` and returns to the caller
+    fn return_value_in_register(&mut self) {
+        let return_register = register_for(self.return_type, RETURN_REGISTER);
+        match self.used_registers.pop() {
+            Some(register) => {
+                if self.check_same_kind(return_register, register, "return") {
+                    self.copy_register(register, return_register);
+                }
+                self.free_register(register);
+            }
+            None => self.load_zero(return_register),
+        }
+        self.assembly.push("RET".to_string());
+    }
+
+    /// Compiles a call of the function `name`. Its value ends up in a fresh
+    /// register.
+    fn call(&mut self, name: &str, arguments: &[Token]) {
+        if name == "print" || name == "println" {
+            self.print(name, arguments);
+            return;
+        }
+        let Some(function) = self.functions.get(name).cloned() else {
+            self.errors.push(CompileError::UndefinedFunction {
+                name: name.to_string(),
+            });
+            self.zero_value();
+            return;
+        };
+        let parameters = &function.signature.parameters;
+        if arguments.len() != parameters.len() {
+            self.errors.push(CompileError::ArgumentCountMismatch {
+                name: name.to_string(),
+                expected: parameters.len(),
+                found: arguments.len(),
+            });
+        }
+
+        // Every argument is evaluated before any is moved into place, as
+        // evaluating one may involve another call
+        let depth = self.used_registers.len();
+        for argument in arguments {
+            self.visit_token(argument);
+        }
+        let argument_values = self.used_registers.split_off(depth);
+        for ((value_register, ty), argument_register) in argument_values
+            .into_iter()
+            .zip(parameters.iter().copied())
+            .zip(ARGUMENT_REGISTERS)
+        {
+            let argument_register = register_for(ty, argument_register);
+            if self.check_same_kind(argument_register, value_register, name) {
+                self.copy_register(value_register, argument_register);
+            }
+            self.free_register(value_register);
+        }
+
+        // lrvm cannot push a float register on the stack. Inside a function
+        // the floats in use are in the same registers the function called,
+        // or the function itself, may use, so there is no keeping them.
+        let highest_live_float = (0..ARGUMENT_REGISTERS.start)
+            .filter(|register| !self.free_float_registers.contains(register))
+            .max();
+        if self.in_function {
+            self.callees.push(name.to_string());
+            if highest_live_float.is_some() {
+                self.errors.push(CompileError::FloatLiveAcrossCall {
+                    name: name.to_string(),
+                });
+            }
+        } else if let Some(highest) = highest_live_float {
+            self.float_calls.push((name.to_string(), highest));
+        }
+        let live_registers: Vec<u8> = (0..ARGUMENT_REGISTERS.start)
+            .filter(|register| !self.free_registers.contains(register))
+            .collect();
+        for register in &live_registers {
+            self.assembly.push(format!("PUSH ${}", register));
+        }
+        self.assembly.push(format!("CALL @{}", function.label));
+        for register in live_registers.iter().rev() {
+            self.assembly.push(format!("POP ${}", register));
+        }
+
+        let return_register = register_for(function.signature.returns, RETURN_REGISTER);
+        let result_register = self.allocate_like(return_register);
+        self.copy_register(return_register, result_register);
+        self.used_registers.push(result_register);
+    }
+
+    /// Reports the calls made while float registers were in use that the
+    /// function called, or a function it calls in turn, may overwrite
+    fn check_float_calls(&mut self) {
+        for (name, highest) in std::mem::take(&mut self.float_calls) {
+            if self.float_floor_of(&name) <= highest {
+                self.errors.push(CompileError::FloatLiveAcrossCall { name });
+            }
+        }
+    }
+
+    /// The lowest float register calling `name` may overwrite
+    fn float_floor_of(&self, name: &str) -> u8 {
+        let mut floor = ARGUMENT_REGISTERS.start;
+        let mut reached = vec![name.to_string()];
+        let mut i = 0;
+        while i < reached.len() {
+            if let Some(function) = self.functions.get(&reached[i]) {
+                floor = floor.min(function.float_floor);
+                for callee in &function.callees {
+                    if !reached.contains(callee) {
+                        reached.push(callee.clone());
+                    }
+                }
+            }
+            i += 1;
+        }
+        floor
+    }
+
+    /// Compiles the `print` and `println` builtins. A string literal is
+    /// written out with PRTS and any other value by a runtime routine.
+    /// `println` adds a newline, and can also be called without an argument
+    /// to write just that. Neither has a value of its own, so they give 0.
+    fn print(&mut self, name: &str, arguments: &[Token]) {
+        let allowed = if name == "println" { 0..=1 } else { 1..=1 };
+        if !allowed.contains(&arguments.len()) {
+            self.errors.push(CompileError::ArgumentCountMismatch {
+                name: name.to_string(),
+                expected: 1,
+                found: arguments.len(),
+            });
+        }
+        for argument in arguments {
+            match argument.as_string() {
+                Some(text) => self.print_string(text),
+                None => {
+                    self.visit_token(argument);
+                    if let Some(register) = self.used_registers.pop() {
+                        let (argument_register, routine) = if register.is_float() {
+                            (
+                                Register::Float(ARGUMENT_REGISTERS.start),
+                                Routine::PrintFloat,
+                            )
+                        } else {
+                            (Register::Int(ARGUMENT_REGISTERS.start), Routine::PrintInt)
+                        };
+                        self.copy_register(register, argument_register);
+                        self.free_register(register);
+                        self.call_routine(routine);
+                    }
+                }
+            }
+        }
+        if name == "println" {
+            self.print_string("\n");
+        }
+        self.zero_value();
+    }
+
+    /// Writes `text` out with PRTS
+    fn print_string(&mut self, text: &str) {
+        if let Some(label) = self.intern_string(text) {
+            self.assembly.push(format!("PRTS @{}", label));
+        }
+    }
+
+    /// Calls the runtime routine `routine`, linking it in first if needed.
+    /// Routines leave the allocation pool alone, so no registers are saved.
+    fn call_routine(&mut self, routine: Routine) {
+        self.link(routine);
+        self.assembly.push(format!("CALL @{}", routine.label()));
+    }
+
+    /// Adds `routine` and the routines it calls to `function_assembly`,
+    /// unless they are there already
+    fn link(&mut self, routine: Routine) {
+        if self.routines.contains(&routine) {
+            return;
+        }
+        self.routines.push(routine);
+        for dependency in routine.dependencies() {
+            self.link(*dependency);
+        }
+        let mut string_labels = HashMap::new();
+        for (name, text) in routine.strings() {
+            if let Some(label) = self.intern_string(&text) {
+                string_labels.insert(name, label);
+            }
+        }
+        self.function_assembly
+            .extend(routine.assembly(&string_labels));
+    }
+
+    /// The label of `text` in the `.data` section, adding it there if this
+    /// is the first time it is used
+    fn intern_string(&mut self, text: &str) -> Option<String> {
+        if let Some(label) = self.strings.get(text) {
+            return Some(label.clone());
+        }
+        let label = self.new_label("str");
+        self.data.push(asciiz(&label, text));
+        self.strings.insert(text.to_string(), label.clone());
+        Some(label)
+    }
+
+    /// Pushes a fresh register holding 0, standing in for a value
+    fn zero_value(&mut self) {
+        let register = self.allocate_register();
+        self.load_zero(register);
+        self.used_registers.push(register);
+    }
+
+    /// Compiles one branch of an `if` and moves its value, or 0 if it has
+    /// none, into `result_register`
+    fn branch_value(&mut self, branch: &Token, result_register: Register) {
+        let depth = self.used_registers.len();
+        self.visit_token(branch);
+        if self.used_registers.len() > depth {
+            let value_register = self.used_registers.pop().unwrap();
+            if self.check_same_kind(result_register, value_register, "if") {
+                self.copy_register(value_register, result_register);
+            }
+            self.free_register(value_register);
+        } else {
+            self.load_zero(result_register);
+        }
+        self.discard_values(depth);
+    }
+
+    /// Evaluates `condition` and jumps to `label` when it is false, i.e. 0
+    fn branch_if_false(&mut self, condition: &Token, label: &str) {
+        self.branch_if(condition, label, false);
+    }
+
+    /// Evaluates `condition` and jumps to `label` when it is `when`. A
+    /// comparison jumps on the comparison, or its opposite, directly instead
+    /// of first turning its result into a 0 or 1. `&&` and `||` branch on
+    /// each side in turn, so the right side is skipped once the left side
+    /// has decided where to go.
+    fn branch_if(&mut self, condition: &Token, label: &str, when: bool) {
+        match condition.unwrapped() {
+            Token::Comparison {
+                left,
+                operator,
+                right,
+                ..
+            } => {
+                let opcode = if when {
+                    comparison_opcode(operator)
+                } else {
+                    inverse_comparison_opcode(operator)
+                };
+                if let Some(opcode) = opcode {
+                    self.visit_token(left);
+                    self.visit_token(right);
+                    let right_register = self.used_registers.pop().unwrap();
+                    let left_register = self.used_registers.pop().unwrap();
+                    self.check_same_kind(left_register, right_register, &operator.to_string());
+                    self.assembly.push(format!(
+                        "{}{} ${} ${}",
+                        opcode,
+                        float_suffix(left_register),
+                        left_register,
+                        right_register
+                    ));
+                    self.assembly.push(format!("DJMPE @{}", label));
+                    self.free_register(right_register);
+                    self.free_register(left_register);
+                    return;
+                }
+            }
+            Token::Logical {
+                left,
+                operator,
+                right,
+                ..
+            } => {
+                // `a && b` is false as soon as `a` is, `a || b` true as soon
+                // as `a` is
+                let decided_by = **operator == Token::OrOperator;
+                if when == decided_by {
+                    self.branch_if(left, label, when);
+                    self.branch_if(right, label, when);
+                } else {
+                    let skip_label = self.new_label("skip");
+                    self.branch_if(left, &skip_label, !when);
+                    self.branch_if(right, label, when);
+                    self.assembly.push(format!("{}: NOP", skip_label));
+                }
+                return;
+            }
+            Token::Not { value, .. } => {
+                self.branch_if(value, label, !when);
+                return;
+            }
+            Token::Boolean { value, .. } => {
+                if *value == when {
+                    self.jump(label);
+                }
+                return;
+            }
+            _ => {}
+        }
+
+        self.visit_token(condition);
+        let condition_register = self.used_registers.pop().unwrap();
+        let suffix = float_suffix(condition_register);
+        self.assembly
+            .push(format!("LOAD{} ${} #0", suffix, SCRATCH_REGISTER));
+        self.assembly.push(format!(
+            "{}{} ${} ${}",
+            if when { "NEQ" } else { "EQ" },
+            suffix,
+            condition_register,
+            SCRATCH_REGISTER
+        ));
+        self.assembly.push(format!("DJMPE @{}", label));
+        self.free_register(condition_register);
+    }
+
+    /// Computes the 1 or 0 of a logical operation into a fresh register, by
+    /// branching on it like a condition
+    fn logical_value(&mut self, node: &Token) {
+        let result_register = self.allocate_register();
+        let false_label = self.new_label("false");
+        self.assembly.push(format!("LOAD ${} #0", result_register));
+        self.branch_if_false(node, &false_label);
+        self.assembly.push(format!("LOAD ${} #1", result_register));
+        self.assembly.push(format!("{}: NOP", false_label));
+        self.used_registers.push(result_register);
+    }
+
+    /// Jumps to `label` unconditionally
+    fn jump(&mut self, label: &str) {
+        self.assembly
+            .push(format!("LOAD ${} @{}", SCRATCH_REGISTER, label));
+        self.assembly.push(format!("JMP ${}", SCRATCH_REGISTER));
+    }
+
+    /// Writes the value in `register` to wherever the binding `name` lives
+    fn store_binding(&mut self, name: &str, register: Register) {
+        match self.bindings.get(name).copied() {
+            Some(Location::Register(destination)) => {
+                if self.check_same_kind(destination, register, "=") {
+                    self.copy_register(register, destination);
+                }
+            }
+            Some(Location::Spill(_)) if register.is_float() => {
+                self.errors.push(CompileError::MixedOperands {
+                    operation: "=".to_string(),
+                });
+            }
+            Some(Location::Spill(offset)) => {
+                self.load_integer(SCRATCH_REGISTER, offset as i64);
+                self.assembly
+                    .push(format!("SETM ${} ${}", SCRATCH_REGISTER, register));
+            }
+            None => self.errors.push(CompileError::UndefinedVariable {
+                name: name.to_string(),
+            }),
+        }
+    }
+
+    /// Compiles `target operator value`, e.g. `x += 1`, which updates the
+    /// binding named by `target` and leaves no value behind
+    fn assignment(&mut self, target: &Token, operator: &Token, value: &Token) {
+        let Some(name) = target.as_identifier() else {
+            self.errors.push(CompileError::InvalidAssignmentTarget {
+                target: target.to_string(),
+            });
+            return;
+        };
+
+        let opcode = match operator {
+            Token::AddAssignOperator => Some("ADD"),
+            Token::SubtractAssignOperator => Some("SUB"),
+            Token::MultiplyAssignOperator => Some("MUL"),
+            Token::DivideAssignOperator => Some("DIV"),
+            _ => None,
+        };
+        if let Some(opcode) = opcode {
+            self.load_binding(name);
+            self.visit_token(value);
+            self.binary_operation(opcode);
+        } else {
+            self.visit_token(value);
+        }
+
+        if let Some(register) = self.used_registers.pop() {
+            self.store_binding(name, register);
+            self.free_register(register);
+        }
+    }
+
+    /// Loads the value of the binding `name` into a fresh register
+    fn load_binding(&mut self, name: &str) {
+        let register = match self.bindings.get(name).copied() {
+            Some(Location::Register(source)) => {
+                let register = self.allocate_like(source);
+                self.copy_register(source, register);
+                register
+            }
+            Some(Location::Spill(offset)) => {
+                let register = self.allocate_register();
+                self.load_integer(SCRATCH_REGISTER, offset as i64);
+                self.assembly
+                    .push(format!("LOADM ${} ${}", SCRATCH_REGISTER, register));
+                register
+            }
+            None => {
+                self.errors.push(CompileError::UndefinedVariable {
+                    name: name.to_string(),
+                });
+                let register = self.allocate_register();
+                self.load_zero(register);
+                register
+            }
+        };
+        self.used_registers.push(register);
+    }
+
+    /// Pops the right and then the left operand and emits `opcode` with the
+    /// result going into a fresh register. Floats use the opcode's F64 form.
+    fn binary_operation(&mut self, opcode: &str) {
+        let right_register = self.used_registers.pop().unwrap();
+        let left_register = self.used_registers.pop().unwrap();
+        let result_register = self.allocate_like(left_register);
+        self.check_same_kind(left_register, right_register, opcode_symbol(opcode));
+        let line = format!(
+            "{}{} ${} ${} ${}",
+            opcode,
+            float_suffix(left_register),
+            left_register,
+            right_register,
+            result_register
+        );
+        self.assembly.push(line);
+        self.used_registers.push(result_register);
+        self.free_register(right_register);
+        self.free_register(left_register);
+    }
+
+    /// Like `binary_operation`, for an `opcode` that only works on integers
+    fn integer_operation(&mut self, opcode: &str) {
+        self.check_integer_operands(opcode_symbol(opcode));
+        self.binary_operation(opcode);
+    }
+
+    /// Reports an error if either of the two operands on top of the stack of
+    /// used registers is a float
+    fn check_integer_operands(&mut self, operation: &str) -> bool {
+        let floats = self
+            .used_registers
+            .iter()
+            .rev()
+            .take(2)
+            .any(|register| register.is_float());
+        if floats {
+            self.errors.push(CompileError::IntegerOnly {
+                operation: operation.to_string(),
+            });
+        }
+        !floats
+    }
+
+    /// Pops the right and then the left operand and leaves the remainder of
+    /// their division in a fresh register, worked out as `left - left /
+    /// right * right`: lrvm's DIV keeps the remainder in a register of its
+    /// own, but no instruction reads it back.
+    fn remainder(&mut self) {
+        self.check_integer_operands("%");
+        let right_register = self.used_registers.pop().unwrap();
+        let left_register = self.used_registers.pop().unwrap();
+        let result_register = self.allocate_register();
+        self.assembly.push(format!(
+            "DIV ${} ${} ${}",
+            left_register, right_register, result_register
+        ));
+        self.assembly.push(format!(
+            "MUL ${} ${} ${}",
+            result_register, right_register, result_register
+        ));
+        self.assembly.push(format!(
+            "SUB ${} ${} ${}",
+            left_register, result_register, result_register
+        ));
+        self.used_registers.push(result_register);
+        self.free_register(right_register);
+        self.free_register(left_register);
+    }
+
+    /// Pops the exponent and then the base, and multiplies 1 by the base as
+    /// many times as the exponent says into a fresh register. The base may be
+    /// a float, the exponent is an integer. A negative exponent gives
+    /// `1 / base ** -exponent`, which for an integer base truncates to 0
+    /// unless the base is 1 or -1, and divides by zero for a base of 0.
+    fn power(&mut self) {
+        let exponent_register = self.used_registers.pop().unwrap();
+        let base_register = self.used_registers.pop().unwrap();
+        if exponent_register.is_float() {
+            self.errors.push(CompileError::IntegerOnly {
+                operation: "**".to_string(),
+            });
+        }
+        let suffix = float_suffix(base_register);
+        // Raise `1 / base` to the negated exponent instead
+        let positive_label = self.new_label("positiveexponent");
+        self.assembly.push(format!("LOAD ${} #0", SCRATCH_REGISTER));
+        self.assembly
+            .push(format!("GTE ${} ${}", exponent_register, SCRATCH_REGISTER));
+        self.assembly.push(format!("DJMPE @{}", positive_label));
+        self.assembly.push(format!(
+            "SUB ${} ${} ${}",
+            SCRATCH_REGISTER, exponent_register, exponent_register
+        ));
+        self.assembly
+            .push(format!("LOAD{} ${} #1", suffix, SCRATCH_REGISTER));
+        self.assembly.push(format!(
+            "DIV{} ${} ${} ${}",
+            suffix, SCRATCH_REGISTER, base_register, base_register
+        ));
+        self.assembly.push(format!("{}: NOP", positive_label));
+
+        let result_register = self.allocate_like(base_register);
+        self.assembly
+            .push(format!("LOAD{} ${} #1", suffix, result_register));
+        self.repeat(
+            exponent_register,
+            vec![format!(
+                "MUL{} ${} ${} ${}",
+                suffix, result_register, base_register, result_register
+            )],
+        );
+        self.used_registers.push(result_register);
+        self.free_register(exponent_register);
+        self.free_register(base_register);
+    }
+
+    /// Pops the shift amount and then the value and shifts the value with
+    /// SHL or SHR. Those only shift by a count written into the instruction,
+    /// so the value is shifted by 16, 16, 8, 4, 2 and 1 bits in turn,
+    /// skipping each step the amount left over is too small for. An amount
+    /// of 32 or more shifts every bit out, and a negative amount shifts
+    /// nothing. SHR is arithmetic, so `>>` rounds towards negative infinity.
+    fn shift(&mut self, left: bool) {
+        self.check_integer_operands(if left { "<<" } else { ">>" });
+        let amount_register = self.used_registers.pop().unwrap();
+        let value_register = self.used_registers.pop().unwrap();
+        let opcode = if left { "SHL" } else { "SHR" };
+        for step in [16, 16, 8, 4, 2, 1] {
+            let skip_label = self.new_label("shift");
+            self.assembly
+                .push(format!("LOAD ${} #{}", SCRATCH_REGISTER, step));
+            self.assembly
+                .push(format!("LT ${} ${}", amount_register, SCRATCH_REGISTER));
+            self.assembly.push(format!("DJMPE @{}", skip_label));
+            self.assembly
+                .push(shift_line(opcode, value_register.number(), step));
+            self.assembly.push(format!(
+                "SUB ${} ${} ${}",
+                amount_register, SCRATCH_REGISTER, amount_register
+            ));
+            self.assembly.push(format!("{}: NOP", skip_label));
+        }
+        self.used_registers.push(value_register);
+        self.free_register(amount_register);
+    }
+
+    /// Emits a loop that runs `body` as many times as the integer in `count`
+    /// says, counting the register down to 0. A count below 1 skips the loop.
+    fn repeat(&mut self, count: Register, body: Vec<String>) {
+        let head_label = self.new_label("repeat");
+        let exit_label = self.new_label("endrepeat");
+        self.assembly.push(format!("{}: NOP", head_label));
+        self.assembly.push(format!("LOAD ${} #0", SCRATCH_REGISTER));
+        self.assembly
+            .push(format!("LTE ${} ${}", count, SCRATCH_REGISTER));
+        self.assembly.push(format!("DJMPE @{}", exit_label));
+        self.assembly.extend(body);
+        self.assembly.push(format!("LOAD ${} #1", SCRATCH_REGISTER));
+        self.assembly
+            .push(format!("SUB ${} ${} ${}", count, SCRATCH_REGISTER, count));
+        self.jump(&head_label);
+        self.assembly.push(format!("{}: NOP", exit_label));
+    }
+
+    /// Negates the value on top of the stack of used registers in place, by
+    /// subtracting it from 0
+    fn negate(&mut self) {
+        let Some(&register) = self.used_registers.last() else {
+            return;
+        };
+        let suffix = float_suffix(register);
+        self.assembly
+            .push(format!("LOAD{} ${} #0", suffix, SCRATCH_REGISTER));
+        self.assembly.push(format!(
+            "SUB{} ${} ${} ${}",
+            suffix, SCRATCH_REGISTER, register, register
+        ));
+    }
+
+    /// Pops the right and then the left operand and compares them with
+    /// `opcode`. The comparison only sets the VM's equality flag, so the flag
+    /// is turned into a 1 or 0 in a fresh register, for floats as well.
+    fn comparison(&mut self, opcode: &str) {
+        let right_register = self.used_registers.pop().unwrap();
+        let left_register = self.used_registers.pop().unwrap();
+        let result_register = self.allocate_register();
+        self.check_same_kind(left_register, right_register, opcode_symbol(opcode));
+        let done = self.new_label("cmp");
+        self.assembly.push(format!("LOAD ${} #1", result_register));
+        self.assembly.push(format!(
+            "{}{} ${} ${}",
+            opcode,
+            float_suffix(left_register),
+            left_register,
+            right_register
+        ));
+        self.assembly.push(format!("DJMPE @{}", done));
+        self.assembly.push(format!("LOAD ${} #0", result_register));
+        self.assembly.push(format!("{}: NOP", done));
+        self.used_registers.push(result_register);
+        self.free_register(right_register);
+        self.free_register(left_register);
+    }
+
+    /// Emits the instructions to put `value` into `register`
+    fn load_integer(&mut self, register: u8, value: i64) {
+        self.assembly.extend(load_integer_lines(register, value));
+    }
+
+    /// Emits the instructions to put `value` into the float `register`.
+    /// LOADF64 only takes a 16-bit integer, so the float is rebuilt from its
+    /// shortest decimal form: the digits as a whole number, four at a time,
+    /// multiplied or divided by a power of ten. For literals of up to 15
+    /// digits and exponents up to 22 both numbers are exact, and the result
+    /// is the float the literal stands for.
+    fn load_float(&mut self, register: Register, value: f64) {
+        let (digits, exponent) = decimal_parts(value);
+        let first = digits.len() % 4;
+        let first = if first == 0 { 4 } else { first };
+        self.assembly
+            .push(format!("LOADF64 ${} #{}", register, &digits[..first]));
+        for chunk in digits.as_bytes()[first..].chunks(4) {
+            let chunk = std::str::from_utf8(chunk).unwrap_or("0");
+            self.assembly
+                .push(format!("LOADF64 ${} #10000", SCRATCH_REGISTER));
+            self.assembly.push(format!(
+                "MULF64 ${} ${} ${}",
+                register, SCRATCH_REGISTER, register
+            ));
+            self.assembly
+                .push(format!("LOADF64 ${} #{}", SCRATCH_REGISTER, chunk));
+            self.assembly.push(format!(
+                "ADDF64 ${} ${} ${}",
+                register, SCRATCH_REGISTER, register
+            ));
+        }
+
+        if exponent != 0 {
+            let power = self.allocate_float_register();
+            self.assembly.push(format!("LOADF64 ${} #1", power));
+            let mut remaining = exponent.unsigned_abs();
+            while remaining > 0 {
+                let step = remaining.min(4);
+                self.assembly.push(format!(
+                    "LOADF64 ${} #{}",
+                    SCRATCH_REGISTER,
+                    10u32.pow(step)
+                ));
+                self.assembly.push(format!(
+                    "MULF64 ${} ${} ${}",
+                    power, SCRATCH_REGISTER, power
+                ));
+                remaining -= step;
             }
+            let opcode = if exponent > 0 { "MULF64" } else { "DIVF64" };
+            self.assembly
+                .push(format!("{} ${} ${} ${}", opcode, register, power, register));
+            self.free_register(power);
+        }
+
+        if value < 0.0 {
+            self.assembly
+                .push(format!("LOADF64 ${} #0", SCRATCH_REGISTER));
+            self.assembly.push(format!(
+                "SUBF64 ${} ${} ${}",
+                SCRATCH_REGISTER, register, register
+            ));
         }
     }
 
     pub fn print_asm(&self) {
         for line in &self.assembly {
-            println!("{:#?}", line);
+            println!("{}", line);
         }
     }
 
@@ -87,100 +1374,341 @@ impl Compiler {
             println!("{:#?}", r);
         }
     }
+
+    pub fn print_free_float_registers(&self) {
+        println!("--------------------------");
+        println!("|  Free Float Registers  |");
+        println!("--------------------------");
+        for r in &self.free_float_registers {
+            println!("{:#?}", r);
+        }
+    }
+}
+
+/// The lrvm instruction for a comparison operator
+fn comparison_opcode(operator: &Token) -> Option<&'static str> {
+    match operator {
+        Token::EqualOperator => Some("EQ"),
+        Token::NotEqualOperator => Some("NEQ"),
+        Token::GreaterThanOperator => Some("GT"),
+        Token::LessThanOperator => Some("LT"),
+        Token::GreaterThanOrEqualOperator => Some("GTE"),
+        Token::LessThanOrEqualOperator => Some("LTE"),
+        _ => None,
+    }
+}
+
+/// The lrvm instruction that is true exactly when the comparison `operator`
+/// is false
+fn inverse_comparison_opcode(operator: &Token) -> Option<&'static str> {
+    match operator {
+        Token::EqualOperator => Some("NEQ"),
+        Token::NotEqualOperator => Some("EQ"),
+        Token::GreaterThanOperator => Some("LTE"),
+        Token::LessThanOperator => Some("GTE"),
+        Token::GreaterThanOrEqualOperator => Some("LT"),
+        Token::LessThanOrEqualOperator => Some("GT"),
+        _ => None,
+    }
+}
+
+/// The register numbered `number` in the register file values of type `ty`
+/// are kept in
+fn register_for(ty: Type, number: u8) -> Register {
+    match ty {
+        Type::Float => Register::Float(number),
+        _ => Register::Int(number),
+    }
+}
+
+/// The suffix that turns an instruction into the one for floats when
+/// `register` is a float register, e.g. ADD into ADDF64
+fn float_suffix(register: Register) -> &'static str {
+    if register.is_float() {
+        "F64"
+    } else {
+        ""
+    }
+}
+
+/// The source symbol of an arithmetic or comparison instruction, for errors
+fn opcode_symbol(opcode: &str) -> &'static str {
+    match opcode {
+        "ADD" => "+",
+        "SUB" => "-",
+        "MUL" => "*",
+        "DIV" => "/",
+        "AND" => "&",
+        "OR" => "|",
+        "XOR" => "^",
+        "EQ" => "==",
+        "NEQ" => "!=",
+        "GT" => ">",
+        "LT" => "<",
+        "GTE" => ">=",
+        "LTE" => "<=",
+        _ => "?",
+    }
+}
+
+/// The digits of the shortest decimal form of `value`'s magnitude and the
+/// power of ten they are scaled by, e.g. `("314", -2)` for `-3.14`
+fn decimal_parts(value: f64) -> (String, i32) {
+    let scientific = format!("{:e}", value.abs());
+    let (mantissa, exponent) = scientific.split_once('e').unwrap_or((&scientific, "0"));
+    let exponent: i32 = exponent.parse().unwrap_or(0);
+    let (whole, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    (
+        format!("{}{}", whole, fraction),
+        exponent - fraction.len() as i32,
+    )
+}
+
+/// The registers the `Compiler` allocates from, handed out lowest first
+fn register_pool() -> Vec<u8> {
+    (0..ARGUMENT_REGISTERS.start).rev().collect()
+}
+
+/// The `.asciiz` line that puts `text` at `label` in the `.data` section.
+/// The text goes between single quotes, with `\\`, `'` and line breaks
+/// escaped so that the directive stays on one line.
+pub fn asciiz(label: &str, text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\'' => escaped.push_str("\\'"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c => escaped.push(c),
+        }
+    }
+    format!("{}: .asciiz '{}'", label, escaped)
+}
+
+/// An lrvm `SHL` or `SHR` of `register` by `count` bits, 1 to 16. The VM
+/// reads the count from the first byte of the instruction's 16-bit operand,
+/// and takes 0 there for 16.
+fn shift_line(opcode: &str, register: u8, count: u16) -> String {
+    format!("{} ${} #{}", opcode, register, count << 8)
+}
+
+/// The instructions that put `value` into `register`. LOAD only takes an
+/// unsigned 16-bit operand, so larger magnitudes are built up 16 bits at a
+/// time and negative values are subtracted from zero.
+fn load_integer_lines(register: u8, value: i64) -> Vec<String> {
+    let mut lines = vec![];
+    let magnitude = value.unsigned_abs();
+    let mut chunks = vec![];
+    let mut rest = magnitude;
+    loop {
+        chunks.push(rest & 0xFFFF);
+        rest >>= 16;
+        if rest == 0 {
+            break;
+        }
+    }
+
+    let mut chunks = chunks.into_iter().rev();
+    let first = chunks.next().unwrap_or(0);
+    lines.push(format!("LOAD ${} #{}", register, first));
+    for chunk in chunks {
+        // Shift the value built so far up by 16 bits
+        lines.push(format!("LOAD ${} #256", SCRATCH_REGISTER));
+        for _ in 0..2 {
+            lines.push(format!(
+                "MUL ${} ${} ${}",
+                register, SCRATCH_REGISTER, register
+            ));
+        }
+        lines.push(format!("LOAD ${} #{}", SCRATCH_REGISTER, chunk));
+        lines.push(format!(
+            "ADD ${} ${} ${}",
+            register, SCRATCH_REGISTER, register
+        ));
+    }
+
+    if value < 0 {
+        lines.push(format!("LOAD ${} #0", SCRATCH_REGISTER));
+        lines.push(format!(
+            "SUB ${} ${} ${}",
+            SCRATCH_REGISTER, register, register
+        ));
+    }
+    lines
 }
 
 impl Visitor for Compiler {
     fn visit_token(&mut self, node: &Token) {
         match node {
-            Token::AdditionOperator => {
-                println!("Visiting AdditionOperator");
-                let result_register = self.free_registers.pop().unwrap();
-                let left_register = self.used_registers.pop().unwrap();
-                let right_register = self.used_registers.pop().unwrap();
-                let line = format!(
-                    "ADD ${} ${} ${}",
-                    right_register, left_register, result_register
-                );
-                self.assembly.push(line);
-                self.used_registers.push(result_register);
-                self.free_registers.push(left_register);
-                self.free_registers.push(right_register);
-            }
-            Token::SubtractionOperator => {
-                let result_register = self.free_registers.pop().unwrap();
-                let left_register = self.used_registers.pop().unwrap();
-                let right_register = self.used_registers.pop().unwrap();
-                let line = format!(
-                    "SUB ${} ${} ${}",
-                    right_register, left_register, result_register
-                );
-                self.assembly.push(line);
-                self.used_registers.push(result_register);
-                self.free_registers.push(left_register);
-                self.free_registers.push(right_register);
-            }
-            Token::MultiplicationOperator => {
-                let result_register = self.free_registers.pop().unwrap();
-                let left_register = self.used_registers.pop().unwrap();
-                let right_register = self.used_registers.pop().unwrap();
-                let line = format!(
-                    "MUL ${} ${} ${}",
-                    left_register, right_register, result_register
-                );
-                self.assembly.push(line);
-                self.used_registers.push(result_register);
-                self.free_registers.push(left_register);
-                self.free_registers.push(right_register);
-            }
-            Token::DivisionOperator => {
-                let result_register = self.free_registers.pop().unwrap();
-                let left_register = self.used_registers.pop().unwrap();
-                let right_register = self.used_registers.pop().unwrap();
-                let line = format!(
-                    "DIV ${} ${} ${}",
-                    left_register, right_register, result_register
-                );
-                self.assembly.push(line);
-                self.used_registers.push(result_register);
-                self.free_registers.push(left_register);
-                self.free_registers.push(right_register);
-            }
-            Token::Integer { value } => {
-                println!("Visited Integer with value of: {:#?}", value);
-                let next_register = self.free_registers.pop().unwrap();
-                let line = format!("LOAD ${} #{}", next_register, value);
+            Token::AdditionOperator => self.binary_operation("ADD"),
+            Token::SubtractionOperator => self.binary_operation("SUB"),
+            Token::MultiplicationOperator => self.binary_operation("MUL"),
+            Token::DivisionOperator => self.binary_operation("DIV"),
+            Token::ModuloOperator => self.remainder(),
+            Token::BitAndOperator => self.integer_operation("AND"),
+            Token::BitOrOperator => self.integer_operation("OR"),
+            Token::BitXorOperator => self.integer_operation("XOR"),
+            Token::ShiftLeftOperator => self.shift(true),
+            Token::ShiftRightOperator => self.shift(false),
+            // `**` is compiled as part of its `Power`
+            Token::PowerOperator => {}
+            Token::Negation { value, .. } => {
+                self.visit_token(value);
+                self.negate();
+            }
+            Token::Identity { value, .. } => self.visit_token(value),
+            // The parser has already reported what is wrong with the
+            // statement, and a program with one in it is never compiled
+            Token::Error { .. } => {}
+            Token::Power { base, exponent, .. } => {
+                self.visit_token(base);
+                self.visit_token(exponent);
+                self.power();
+            }
+            Token::Bitwise {
+                left,
+                operator,
+                right,
+                ..
+            } => {
+                self.visit_token(left);
+                self.visit_token(right);
+                self.visit_token(operator);
+            }
+            // An annotation can make an integer literal a float
+            Token::Integer { value, .. } if self.types.get(node) == Some(Type::Float) => {
+                let next_register = self.allocate_float_register();
+                self.load_float(next_register, *value as f64);
+                self.used_registers.push(next_register);
+            }
+            Token::Integer { value, .. } => {
+                let next_register = self.allocate_register();
+                self.load_integer(next_register.number(), *value);
                 self.used_registers.push(next_register);
-                self.assembly.push(line);
             }
             Token::Expression {
                 ref left,
                 ref right,
+                ..
             } => {
-                println!("Visiting an expression");
                 self.visit_token(left);
                 for term in right {
                     self.visit_token(&term.1);
                     self.visit_token(&term.0);
                 }
-                println!("Done visiting expression");
             }
-            Token::Program { ref expressions } => {
-                println!("Visiting program");
-                for expression in expressions {
-                    self.visit_token(expression);
+            Token::Program { ref statements, .. } => {
+                // Functions can be called anywhere in the program, even
+                // before they are defined
+                for statement in statements {
+                    if let Token::Function {
+                        name,
+                        parameters,
+                        return_type,
+                        ..
+                    } = statement
+                    {
+                        self.declare_function(name, parameters, *return_type);
+                    }
+                }
+                for statement in statements {
+                    self.visit_token(statement);
+                    // Only the value of the final statement is reported, so the
+                    // register of every statement can go back to the pool
+                    self.result_register = self.used_registers.pop();
+                    if let Some(register) = self.result_register {
+                        self.free_register(register);
+                    }
+                }
+                self.assembly.push("HLT".to_string());
+                self.check_float_calls();
+            }
+            Token::Identifier { name, .. } => self.load_binding(name),
+            Token::Let { name, value, .. } => {
+                self.visit_token(value);
+                if let Some(register) = self.used_registers.pop() {
+                    self.bind(name, register);
+                }
+            }
+            Token::Assignment {
+                target,
+                operator,
+                value,
+                ..
+            } => self.assignment(target, operator, value),
+            // Assignment operators are compiled as part of their `Assignment`
+            Token::AssignOperator
+            | Token::AddAssignOperator
+            | Token::SubtractAssignOperator
+            | Token::MultiplyAssignOperator
+            | Token::DivideAssignOperator => {}
+            Token::EqualOperator
+            | Token::NotEqualOperator
+            | Token::GreaterThanOperator
+            | Token::LessThanOperator
+            | Token::GreaterThanOrEqualOperator
+            | Token::LessThanOrEqualOperator => {
+                if let Some(opcode) = comparison_opcode(node) {
+                    self.comparison(opcode);
                 }
-                println!("Done visiting program");
             }
-            Token::Float { value } => {
-                let next_register = self.free_registers.pop().unwrap();
-                let line = format!("LOAD ${} #{}", next_register, value);
+            Token::Comparison {
+                left,
+                operator,
+                right,
+                ..
+            } => {
+                self.visit_token(left);
+                self.visit_token(right);
+                self.visit_token(operator);
+            }
+            Token::Logical { .. } | Token::Not { .. } => self.logical_value(node),
+            // Logical operators are compiled as part of their `Logical`
+            Token::AndOperator | Token::OrOperator => {}
+            Token::Boolean { value, .. } => {
+                let next_register = self.allocate_register();
+                self.load_integer(next_register.number(), *value as i64);
                 self.used_registers.push(next_register);
-                self.assembly.push(line);
             }
-            Token::Factor { value } => {
+            Token::Block { statements, .. } => self.block(statements),
+            Token::If {
+                condition,
+                body,
+                alternative,
+                ..
+            } => self.if_expression(node, condition, body, alternative.as_deref()),
+            Token::While {
+                condition, body, ..
+            } => self.while_loop(condition, body),
+            Token::Break { .. } | Token::Continue { .. } => self.loop_control(node),
+            Token::Function {
+                name,
+                parameters,
+                return_type,
+                body,
+                ..
+            } => self.function(name, parameters, *return_type, body),
+            Token::Return { value, .. } => self.return_statement(value.as_deref()),
+            Token::Call {
+                name, arguments, ..
+            } => self.call(name, arguments),
+            Token::String { value, .. } => {
+                self.errors.push(CompileError::UnexpectedString {
+                    value: value.clone(),
+                });
+                self.zero_value();
+            }
+            Token::Float { value, .. } => {
+                let next_register = self.allocate_float_register();
+                self.load_float(next_register, *value);
+                self.used_registers.push(next_register);
+            }
+            Token::Factor { value, .. } => {
                 self.visit_token(value);
             }
-            Token::Term { left, right } => {
+            Token::Term { left, right, .. } => {
                 self.visit_token(&left);
                 for factor in right {
                     self.visit_token(&factor.1);
@@ -193,13 +1721,22 @@ impl Visitor for Compiler {
 
 #[cfg(test)]
 mod tests {
-    use crate::{program_parsers::program_parser, token::Token};
+    use crate::{
+        error::{CompileError, ParseError},
+        program_parsers::{parse_program, program_parser},
+        token::Token,
+    };
 
-    use super::{Compiler, Visitor};
+    use super::{Compiler, Location, Register, Visitor};
 
     fn generate_test_program(source: &str) -> Token {
-        let (_, tree) = program_parser(source).unwrap();
-        tree
+        match program_parser(source.into()) {
+            Ok((_, tree)) => tree,
+            Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => {
+                panic!("{}", ParseError::from(e))
+            }
+            Err(nom::Err::Incomplete(_)) => panic!("{:?} is incomplete", source),
+        }
     }
 
     #[test]
@@ -208,17 +1745,391 @@ mod tests {
         let mut compiler = Compiler::new();
         let test_program = generate_test_program(&source);
         compiler.visit_token(&test_program);
-        let bytecode = compiler.compile();
+        let bytecode = compiler.compile().unwrap();
         println!("({}) bytecodes: {:?}", source, bytecode);
     }
 
+    #[test]
+    fn test_let_binding_takes_a_register() {
+        let mut compiler = Compiler::new();
+        compiler.visit_token(&generate_test_program("let x = 1+2\nx*2"));
+        assert!(compiler.errors().is_empty());
+        assert!(matches!(compiler.binding("x"), Some(Location::Register(_))));
+        assert!(compiler.compile().is_ok());
+    }
+
+    #[test]
+    fn test_let_bindings_spill_when_registers_run_low() {
+        let source: String = (0..30).map(|i| format!("let v{} = {}\n", i, i)).collect();
+        let mut compiler = Compiler::new();
+        compiler.visit_token(&generate_test_program(&source));
+        assert!(compiler.errors().is_empty());
+        assert!(matches!(
+            compiler.binding("v0"),
+            Some(Location::Register(_))
+        ));
+        assert_eq!(Some(Location::Spill(0)), compiler.binding("v16"));
+        assert_eq!(Some(Location::Spill(4)), compiler.binding("v17"));
+        assert!(compiler.assembly().contains("ALOC $31"));
+    }
+
+    #[test]
+    fn test_statements_that_do_not_parse_are_skipped() {
+        // The parser reports these, the compiler adds nothing of its own
+        let program = parse_program("let x = )\n1 @ 2\nlet y = 1\ny")
+            .unwrap_err()
+            .program;
+        let mut compiler = Compiler::new();
+        compiler.visit_token(&program);
+        assert!(compiler.errors().is_empty());
+    }
+
+    #[test]
+    fn test_undefined_variable() {
+        let mut compiler = Compiler::new();
+        compiler.visit_token(&generate_test_program("1 + y"));
+        assert_eq!(
+            Err(CompileError::UndefinedVariable {
+                name: "y".to_string()
+            }),
+            compiler.compile()
+        );
+    }
+
+    #[test]
+    fn test_duplicate_binding() {
+        let mut compiler = Compiler::new();
+        compiler.visit_token(&generate_test_program("let x = 1\nlet x = 2"));
+        assert_eq!(
+            Err(CompileError::DuplicateBinding {
+                name: "x".to_string()
+            }),
+            compiler.compile()
+        );
+    }
+
+    #[test]
+    fn test_assignment_to_non_lvalue() {
+        for (source, target) in [("1 = 2", "1"), ("let x = 1\nx + 1 += 2", "x + 1")] {
+            let mut compiler = Compiler::new();
+            compiler.visit_token(&generate_test_program(source));
+            assert_eq!(
+                Err(CompileError::InvalidAssignmentTarget {
+                    target: target.to_string()
+                }),
+                compiler.compile()
+            );
+        }
+    }
+
+    #[test]
+    fn test_assignment_to_undefined_variable() {
+        let mut compiler = Compiler::new();
+        compiler.visit_token(&generate_test_program("x = 1"));
+        assert_eq!(
+            Err(CompileError::UndefinedVariable {
+                name: "x".to_string()
+            }),
+            compiler.compile()
+        );
+    }
+
+    #[test]
+    fn test_block_bindings_end_with_the_block() {
+        let mut compiler = Compiler::new();
+        compiler.visit_token(&generate_test_program(
+            "let x = 1\nif x > 0 { let y = 2\n y }\nx",
+        ));
+        assert!(compiler.errors().is_empty());
+        assert!(compiler.binding("x").is_some());
+        assert!(compiler.binding("y").is_none());
+
+        let mut compiler = Compiler::new();
+        compiler.visit_token(&generate_test_program("if 1 > 0 { let y = 2 }\ny"));
+        assert_eq!(
+            Err(CompileError::UndefinedVariable {
+                name: "y".to_string()
+            }),
+            compiler.compile()
+        );
+    }
+
+    #[test]
+    fn test_if_branches_on_the_inverse_comparison() {
+        let mut compiler = Compiler::new();
+        compiler.visit_token(&generate_test_program("if 1 > 2 { 3 } else { 4 }"));
+        let assembly = compiler.assembly();
+        assert!(assembly.contains("LTE $1 $2"));
+        assert!(assembly.contains("DJMPE @else0"));
+        assert!(assembly.contains("JMP $31"));
+    }
+
+    #[test]
+    fn test_logical_operators_short_circuit() {
+        let mut compiler = Compiler::new();
+        compiler.visit_token(&generate_test_program("if 1 > 2 || 3 < 4 { 5 }"));
+        let assembly = compiler.assembly();
+        // A true left side skips the right one and goes to the body
+        assert!(assembly.contains("GT $1 $2\nDJMPE @skip2"));
+        assert!(assembly.contains("GTE $1 $2\nDJMPE @else0\nskip2: NOP"));
+
+        let mut compiler = Compiler::new();
+        compiler.visit_token(&generate_test_program("if !(1 > 2) && 3 < 4 { 5 }"));
+        let assembly = compiler.assembly();
+        assert!(assembly.contains("GT $1 $2\nDJMPE @else0"));
+        assert!(assembly.contains("GTE $1 $2\nDJMPE @else0"));
+    }
+
+    #[test]
+    fn test_integer_operators() {
+        let mut compiler = Compiler::new();
+        compiler.visit_token(&generate_test_program("7 % 3"));
+        assert!(compiler
+            .assembly()
+            .contains("DIV $0 $1 $2\nMUL $2 $1 $2\nSUB $0 $2 $2"));
+
+        for (source, shift) in [("1 << 4", "SHL $0 #1024"), ("256 >> 4", "SHR $0 #1024")] {
+            let mut compiler = Compiler::new();
+            compiler.visit_token(&generate_test_program(source));
+            let assembly = compiler.assembly();
+            assert!(assembly.contains(shift), "{}", source);
+            assert!(!assembly.contains("repeat"), "{}", source);
+        }
+
+        for (source, operation) in [("1.5 % 2.0", "%"), ("1.5 & 1.0", "&"), ("2 ** 0.5", "**")] {
+            let mut compiler = Compiler::new();
+            compiler.visit_token(&generate_test_program(source));
+            assert_eq!(
+                Err(CompileError::IntegerOnly {
+                    operation: operation.to_string()
+                }),
+                compiler.compile(),
+                "{}",
+                source
+            );
+        }
+    }
+
+    #[test]
+    fn test_while_jumps_between_head_and_exit_labels() {
+        let mut compiler = Compiler::new();
+        compiler.visit_token(&generate_test_program(
+            "let i = 0\nwhile i < 3 { i += 1\n if i == 2 { continue }\n break }",
+        ));
+        assert!(compiler.errors().is_empty());
+        let assembly = compiler.assembly();
+        assert!(assembly.contains("loop0: NOP"));
+        assert!(assembly.contains("DJMPE @endloop1"));
+        assert!(assembly.contains("LOAD $31 @loop0"));
+        assert!(assembly.contains("LOAD $31 @endloop1"));
+        assert!(assembly.contains("endloop1: NOP"));
+    }
+
+    #[test]
+    fn test_break_outside_loop() {
+        let mut compiler = Compiler::new();
+        compiler.visit_token(&generate_test_program("break"));
+        assert_eq!(
+            Err(CompileError::LoopControlOutsideLoop {
+                keyword: "break".to_string()
+            }),
+            compiler.compile()
+        );
+    }
+
+    #[test]
+    fn test_call_saves_live_registers() {
+        let mut compiler = Compiler::new();
+        compiler.visit_token(&generate_test_program(
+            "fn id(x) { x }\nlet a = 1\na + id(2)",
+        ));
+        assert!(compiler.errors().is_empty());
+        let assembly = compiler.assembly();
+        assert!(assembly.contains("ADD $2 $31 $24"));
+        assert!(assembly.contains("PUSH $0"));
+        assert!(assembly.contains("CALL @fn0"));
+        assert!(assembly.contains("POP $0"));
+        // The function's code comes after the program's HLT
+        let halt = assembly.find("HLT").unwrap();
+        assert!(assembly.find("fn0: NOP").unwrap() > halt);
+        assert!(assembly.find("RET").unwrap() > halt);
+    }
+
+    #[test]
+    fn test_call_errors() {
+        for (source, error) in [
+            (
+                "nope(1)",
+                CompileError::UndefinedFunction {
+                    name: "nope".to_string(),
+                },
+            ),
+            (
+                "fn f(a) { a }\nf(1, 2)",
+                CompileError::ArgumentCountMismatch {
+                    name: "f".to_string(),
+                    expected: 1,
+                    found: 2,
+                },
+            ),
+            (
+                "fn f() { 1 }\nfn f() { 2 }",
+                CompileError::DuplicateFunction {
+                    name: "f".to_string(),
+                },
+            ),
+            (
+                "fn f(a, b, c, d, e, g, h) { 1 }",
+                CompileError::TooManyParameters {
+                    name: "f".to_string(),
+                    limit: 6,
+                },
+            ),
+            (
+                "let x = 1\nfn f() { x }",
+                CompileError::UndefinedVariable {
+                    name: "x".to_string(),
+                },
+            ),
+        ] {
+            let mut compiler = Compiler::new();
+            compiler.visit_token(&generate_test_program(source));
+            assert_eq!(Err(error), compiler.compile(), "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_floats_use_their_own_registers() {
+        let mut compiler = Compiler::new();
+        compiler.visit_token(&generate_test_program("let n = 2\n0.5 * 4.0"));
+        assert!(compiler.errors().is_empty());
+        assert_eq!(Some(Register::Float(2)), compiler.result_register());
+        let assembly = compiler.assembly();
+        assert!(assembly.contains("LOAD $0 #2"));
+        assert!(assembly.contains("LOADF64 $0 #5"));
+        assert!(assembly.contains("DIVF64 $0 $1 $0"));
+        assert!(assembly.contains("MULF64 $0 $1 $2"));
+        assert!(!assembly.contains("#0.5"));
+    }
+
+    #[test]
+    fn test_float_arguments_and_return_values() {
+        let mut compiler = Compiler::new();
+        compiler.visit_token(&generate_test_program(
+            "fn scale(n, x: f64) -> f64 { x * 2.0 }\nscale(1, 0.5)",
+        ));
+        assert!(compiler.errors().is_empty());
+        assert_eq!(Some(Register::Float(0)), compiler.result_register());
+        let assembly = compiler.assembly();
+        // `n` comes in $24 and `x` in float register 25
+        assert!(assembly.contains("ADD $0 $31 $24"));
+        assert!(assembly.contains("ADDF64 $0 $31 $25\nCALL @fn0"));
+        assert!(assembly.contains("fn0: NOP\nLOAD $31 #0\nADD $24 $31 $0"));
+        // Functions take float registers from the top of the pool down
+        assert!(assembly.contains("ADDF64 $25 $31 $23"));
+        // The value comes back in float register 30
+        assert!(assembly.contains("ADDF64 $20 $31 $30\nRET"));
+        assert!(assembly.contains("ADDF64 $30 $31 $0"));
+    }
+
+    #[test]
+    fn test_float_call_errors() {
+        for (source, error) in [
+            (
+                "fn f(a) { a }\nf(1.5)",
+                CompileError::MixedOperands {
+                    operation: "f".to_string(),
+                },
+            ),
+            (
+                "fn f() { 1.5 }",
+                CompileError::MixedOperands {
+                    operation: "return".to_string(),
+                },
+            ),
+            (
+                "fn g() { 1 }\nfn f() {\nlet x = 1.5\ng()\n1\n}",
+                CompileError::FloatLiveAcrossCall {
+                    name: "g".to_string(),
+                },
+            ),
+        ] {
+            let mut compiler = Compiler::new();
+            compiler.visit_token(&generate_test_program(source));
+            assert_eq!(Err(error), compiler.compile(), "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_strings_are_interned_in_the_data_section() {
+        let mut compiler = Compiler::new();
+        compiler.visit_token(&generate_test_program(
+            "print(\"hi\")\nprint(\"there\\n\")\nprint(\"hi\")\nprint(\"it's a \\\\\")",
+        ));
+        assert!(compiler.errors().is_empty());
+        let assembly = compiler.assembly();
+        assert!(assembly.starts_with(
+            ".data\nstr0: .asciiz 'hi'\nstr1: .asciiz 'there\\n'\nstr2: .asciiz 'it\\'s a \\\\'\n.code\n"
+        ));
+        assert_eq!(2, assembly.matches("PRTS @str0").count());
+        assert_eq!(1, assembly.matches("PRTS @str1").count());
+        assert!(compiler.compile().is_ok());
+    }
+
+    #[test]
+    fn test_print_links_runtime_routines_once() {
+        let mut compiler = Compiler::new();
+        compiler.visit_token(&generate_test_program(
+            "let x = 0 - 12\nprintln(x)\nprint(x * 2)\nprintln()",
+        ));
+        assert!(compiler.errors().is_empty());
+        let assembly = compiler.assembly();
+        assert_eq!(2, assembly.matches("CALL @printint").count());
+        assert_eq!(1, assembly.matches("printint: NOP").count());
+        assert_eq!(1, assembly.matches("printpadded: NOP").count());
+        assert_eq!(1, assembly.matches("printdigit: NOP").count());
+        assert!(!assembly.contains("printfloat"));
+        assert_eq!(1, assembly.matches(".asciiz '\\n'").count());
+        assert!(compiler.compile().is_ok());
+    }
+
+    #[test]
+    fn test_string_errors() {
+        for (source, error) in [
+            (
+                "print(1, 2)",
+                CompileError::ArgumentCountMismatch {
+                    name: "print".to_string(),
+                    expected: 1,
+                    found: 2,
+                },
+            ),
+            (
+                "let s = \"text\"",
+                CompileError::UnexpectedString {
+                    value: "text".to_string(),
+                },
+            ),
+            (
+                "fn print() { 1 }",
+                CompileError::DuplicateFunction {
+                    name: "print".to_string(),
+                },
+            ),
+        ] {
+            let mut compiler = Compiler::new();
+            compiler.visit_token(&generate_test_program(source));
+            assert_eq!(Err(error), compiler.compile(), "{}", source);
+        }
+    }
+
     #[test]
     fn test_nested_operators() {
         let source = "(4*3)-1";
         let mut compiler = Compiler::new();
         let test_program = generate_test_program(&source);
         compiler.visit_token(&test_program);
-        let bytecode = compiler.compile();
+        let bytecode = compiler.compile().unwrap();
         println!("({}) bytecodes: {:?}", source, bytecode);
     }
 }